@@ -7,7 +7,11 @@ use crate::item_kind::ItemKind;
 pub const BLOCK_SIZE: f32 = 70.;
 const BLOCK_COLLIDER_SIZE: f32 = 35.;
 
-const BLOCK_Z_INDEX: f32 = 0.;
+pub const BLOCK_Z_INDEX: f32 = 0.;
+pub const WALL_Z_INDEX: f32 = -1.;
+
+/// Walls are drawn darker so they read as being behind the blocks
+const WALL_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
 
 // PLUGINS
 
@@ -31,6 +35,7 @@ impl Plugin for BlockPlugin {
 fn load_block_graphics(
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
     mut block_graphics: ResMut<BlockGraphics>,
 ) {
     block_graphics.tex = asset_server.load("blocks.png");
@@ -44,6 +49,7 @@ fn load_block_graphics(
     );
     let atlas_handle = texture_atlases.add(atlas);
     block_graphics.atlas_handle = atlas_handle;
+    block_graphics.material = materials.add(ColorMaterial::from(block_graphics.tex.clone()));
 }
 
 // RESOURCES
@@ -52,6 +58,8 @@ fn load_block_graphics(
 pub struct BlockGraphics {
     tex: Handle<Image>,
     pub atlas_handle: Handle<TextureAtlas>,
+    /// Draws the meshes of the chunks, which are mapped on the atlas
+    pub material: Handle<ColorMaterial>,
 }
 
 impl Default for BlockGraphics {
@@ -59,6 +67,7 @@ impl Default for BlockGraphics {
         Self {
             tex: DEFAULT_IMAGE_HANDLE.typed(),
             atlas_handle: Handle::<TextureAtlas>::default(),
+            material: Handle::<ColorMaterial>::default(),
         }
     }
 }
@@ -69,11 +78,11 @@ pub struct Block;
 
 // BUNDLES
 
+/// Collider of a block of the grid, which is drawn by the mesh of its chunk
 #[derive(Bundle)]
 pub struct BlockBundle {
-    // rendering
-    sprite: TextureAtlasSprite,
-    texture_atlas: Handle<TextureAtlas>,
+    // physics
+    collider: Collider,
 
     // tags
     kind: ItemKind,
@@ -84,51 +93,14 @@ pub struct BlockBundle {
 }
 
 impl BlockBundle {
-    pub fn new(
-        kind: ItemKind,
-        translation: Vec2,
-        blocks_graphics: &BlockGraphics,
-    ) -> (Self, Collider) {
-        if !kind.is_block() {
-            panic!("Cannot spawn block of non block item {:?}", kind);
-        }
-
-        (
-            Self {
-                kind,
-                sprite: TextureAtlasSprite {
-                    index: kind.to_index(),
-                    custom_size: Some(Vec2::splat(BLOCK_SIZE)),
-                    ..default()
-                },
-                texture_atlas: blocks_graphics.atlas_handle.clone(),
-                block: Block,
-                spatial_bundle: SpatialBundle {
-                    transform: Transform::from_xyz(translation.x, translation.y, BLOCK_Z_INDEX),
-                    ..default()
-                },
-            },
-            Collider::cuboid(BLOCK_COLLIDER_SIZE, BLOCK_COLLIDER_SIZE),
-        )
-    }
-
-    pub fn non_collidable(
-        kind: ItemKind,
-        translation: Vec2,
-        blocks_graphics: &BlockGraphics,
-    ) -> Self {
+    pub fn new(kind: ItemKind, translation: Vec2) -> Self {
         if !kind.is_block() {
             panic!("Cannot spawn block of non block item {:?}", kind);
         }
 
         Self {
+            collider: Collider::cuboid(BLOCK_COLLIDER_SIZE, BLOCK_COLLIDER_SIZE),
             kind,
-            sprite: TextureAtlasSprite {
-                index: kind.to_index(),
-                custom_size: Some(Vec2::splat(BLOCK_SIZE)),
-                ..default()
-            },
-            texture_atlas: blocks_graphics.atlas_handle.clone(),
             block: Block,
             spatial_bundle: SpatialBundle {
                 transform: Transform::from_xyz(translation.x, translation.y, BLOCK_Z_INDEX),
@@ -137,3 +109,13 @@ impl BlockBundle {
         }
     }
 }
+
+// FUNCTIONS
+
+pub fn block_color(brightness: f32) -> Color {
+    Color::WHITE * brightness
+}
+
+pub fn wall_color(brightness: f32) -> Color {
+    WALL_COLOR * brightness
+}
@@ -2,6 +2,11 @@ use bevy::prelude::*;
 
 use crate::player::Player;
 
+// CONSTANTS
+
+/// The camera sits at z 999.9, this lets it see below 0 where walls and the sky are drawn
+const CAMERA_FAR: f32 = 2000.;
+
 // PLUGINS
 
 pub struct CamPlugin;
@@ -16,7 +21,10 @@ impl Plugin for CamPlugin {
 // SYSTEMS
 
 fn spawn_camera(mut commands: Commands) {
-    commands.spawn((Camera2dBundle::default(), MainCamera));
+    let mut camera = Camera2dBundle::default();
+    camera.projection.far = CAMERA_FAR;
+
+    commands.spawn((camera, MainCamera));
 }
 
 fn camera_follow_player(
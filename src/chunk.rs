@@ -0,0 +1,579 @@
+use bevy::{
+    ecs::{event::ManualEventReader, system::SystemParam},
+    math::vec2,
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+    utils::HashMap,
+};
+
+use crate::{
+    block::{
+        block_color, wall_color, BlockBundle, BlockGraphics, BLOCK_SIZE, BLOCK_Z_INDEX,
+        WALL_Z_INDEX,
+    },
+    fluid::SOURCE_LEVEL,
+    item_kind::ItemKind,
+    light::{Daylight, Light},
+    save::WorldSave,
+    world::{chunk_of, ChunkPosition, CHUNK_SIZE, WORLD_OFFSET},
+};
+
+// CONSTANTS
+
+pub const CHUNK_HEIGHT: i32 = 256;
+const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_HEIGHT) as usize;
+
+// PLUGINS
+
+pub struct ChunkPlugin;
+
+impl Plugin for ChunkPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Resources
+            .insert_resource(LoadedChunks::default())
+            // Events
+            .add_event::<BlockUpdate>()
+            // Systems
+            .add_systems(PostUpdate, render_chunks);
+    }
+}
+
+// SYSTEMS
+
+/// Keeps the colliders of every chunk in sync with its grid, only respawning the ones of the cells
+/// that changed since the last frame, and builds the meshes of the chunks drawn differently again
+pub fn render_chunks(
+    mut commands: Commands,
+    mut chunks: Query<(Entity, &ChunkPosition, &mut ChunkData, &mut ChunkView), Changed<ChunkData>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    block_graphics: Res<BlockGraphics>,
+    daylight: Res<Daylight>,
+) {
+    let Some(atlas) = texture_atlases.get(&block_graphics.atlas_handle) else { return };
+
+    for (chunk_ent, chunk_pos, mut data, mut view) in chunks.iter_mut() {
+        let redraw = data.take_redraw();
+        let origin = chunk_origin(chunk_pos.0);
+
+        for i in data.take_dirty() {
+            if let Some(collider_ent) = view.colliders[i].take() {
+                commands.entity(collider_ent).despawn_recursive();
+            }
+
+            let local = ChunkData::position(i);
+            let Some(kind) = data.get_block(local.x, local.y) else { continue };
+
+            // Only blocks touching a non solid cell can ever be collided with
+            if !kind.is_collidable() || !data.is_exposed(local.x, local.y) {
+                continue;
+            }
+
+            let translation = IVec2::new(origin + local.x, local.y).as_vec2() * BLOCK_SIZE;
+            let collider_ent = commands.spawn(BlockBundle::new(kind, translation)).id();
+
+            commands.entity(chunk_ent).add_child(collider_ent);
+            view.colliders[i] = Some(collider_ent);
+        }
+
+        if !redraw {
+            continue;
+        }
+
+        let mut blocks = ChunkMesh::default();
+        let mut walls = ChunkMesh::default();
+
+        for i in 0..CHUNK_AREA {
+            let local = ChunkData::position(i);
+            let center = IVec2::new(origin + local.x, local.y).as_vec2() * BLOCK_SIZE;
+            let brightness = data.light[i].brightness(daylight.0);
+
+            // Walls are only seen through empty cells and plants
+            if let Some(wall_kind) = data.get_wall(local.x, local.y) {
+                if !data.is_solid(local.x, local.y) {
+                    walls.push_quad(
+                        center,
+                        Vec2::splat(BLOCK_SIZE),
+                        atlas.textures[wall_kind.to_index()],
+                        wall_color(brightness),
+                    );
+                }
+            }
+
+            let Some(kind) = data.get_block(local.x, local.y) else { continue };
+
+            // Fluids are drawn as high as their level
+            let height = if kind.is_fluid() {
+                BLOCK_SIZE * data.get_level(local.x, local.y) as f32 / SOURCE_LEVEL as f32
+            } else {
+                BLOCK_SIZE
+            };
+
+            blocks.push_quad(
+                center - vec2(0., (BLOCK_SIZE - height) / 2.),
+                vec2(BLOCK_SIZE, height),
+                atlas.textures[kind.to_index()],
+                block_color(brightness),
+            );
+        }
+
+        let view = &mut *view;
+
+        for (mesh, handle, z, name) in [
+            (blocks, &mut view.block_mesh, BLOCK_Z_INDEX, "Blocks"),
+            (walls, &mut view.wall_mesh, WALL_Z_INDEX, "Walls"),
+        ] {
+            let mesh = mesh.build(atlas.size);
+
+            match handle.as_ref().and_then(|handle| meshes.get_mut(handle)) {
+                Some(drawn) => *drawn = mesh,
+                None => {
+                    let mesh_handle = meshes.add(mesh);
+                    let mesh_ent = commands
+                        .spawn((
+                            MaterialMesh2dBundle {
+                                mesh: Mesh2dHandle(mesh_handle.clone()),
+                                material: block_graphics.material.clone(),
+                                transform: Transform::from_xyz(0., 0., z),
+                                ..default()
+                            },
+                            Name::new(name),
+                        ))
+                        .id();
+
+                    commands.entity(chunk_ent).add_child(mesh_ent);
+                    *handle = Some(mesh_handle);
+                }
+            }
+        }
+    }
+}
+
+// RESOURCES
+
+#[derive(Resource, Default)]
+pub struct LoadedChunks(pub HashMap<ChunkPosition, Entity>);
+
+// COMPONENTS
+
+/// Blocks and walls of a chunk stored column by column,
+/// x going from 0 to [`CHUNK_SIZE`] and y from 0 to [`CHUNK_HEIGHT`]
+#[derive(Component)]
+pub struct ChunkData {
+    blocks: Box<[Option<ItemKind>; CHUNK_AREA]>,
+    walls: Box<[Option<ItemKind>; CHUNK_AREA]>,
+    /// Level of the fluid blocks, 0 for every other cell
+    levels: Box<[u8; CHUNK_AREA]>,
+    light: Box<[Light; CHUNK_AREA]>,
+    /// Cells whose collider may have to change
+    dirty: Vec<usize>,
+    /// Whether the chunk is drawn differently since its meshes were built
+    redraw: bool,
+    rebuild: bool,
+}
+
+/// One mesh drawing the blocks of the [`ChunkData`] of the chunk and one drawing its walls,
+/// with the collider entity of each of its exposed blocks
+#[derive(Component)]
+pub struct ChunkView {
+    block_mesh: Option<Handle<Mesh>>,
+    wall_mesh: Option<Handle<Mesh>>,
+    colliders: Box<[Option<Entity>; CHUNK_AREA]>,
+}
+
+impl ChunkData {
+    fn index(x: i32, y: i32) -> Option<usize> {
+        if !(0..CHUNK_SIZE).contains(&x) || !(0..CHUNK_HEIGHT).contains(&y) {
+            return None;
+        }
+
+        Some((x * CHUNK_HEIGHT + y) as usize)
+    }
+
+    fn position(index: usize) -> IVec2 {
+        IVec2::new(index as i32 / CHUNK_HEIGHT, index as i32 % CHUNK_HEIGHT)
+    }
+
+    pub fn get_block(&self, x: i32, y: i32) -> Option<ItemKind> {
+        Self::index(x, y).and_then(|i| self.blocks[i])
+    }
+
+    /// Cells outside of the chunk are ignored, fluids are placed as sources
+    pub fn set_block(&mut self, x: i32, y: i32, kind: Option<ItemKind>) {
+        let Some(i) = Self::index(x, y) else { return };
+
+        self.blocks[i] = kind;
+        self.levels[i] = if kind.is_some_and(|kind| kind.is_fluid()) {
+            SOURCE_LEVEL
+        } else {
+            0
+        };
+        self.redraw = true;
+
+        if self.rebuild {
+            return;
+        }
+
+        // Neighbours may gain or lose their collider
+        for (dx, dy) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+            if let Some(i) = Self::index(x + dx, y + dy) {
+                self.dirty.push(i);
+            }
+        }
+    }
+
+    pub fn get_level(&self, x: i32, y: i32) -> u8 {
+        Self::index(x, y).map_or(0, |i| self.levels[i])
+    }
+
+    /// Changes the level of a fluid block, cells holding anything else are ignored
+    pub fn set_level(&mut self, x: i32, y: i32, level: u8) {
+        let Some(i) = Self::index(x, y) else { return };
+
+        if !self.blocks[i].is_some_and(|kind| kind.is_fluid()) {
+            return;
+        }
+
+        self.levels[i] = level;
+        self.redraw = true;
+    }
+
+    pub fn get_light(&self, x: i32, y: i32) -> Light {
+        Self::index(x, y).map_or(Light::default(), |i| self.light[i])
+    }
+
+    /// Cells outside of the chunk are ignored
+    pub fn set_light(&mut self, x: i32, y: i32, light: Light) {
+        let Some(i) = Self::index(x, y) else { return };
+
+        if self.light[i] == light {
+            return;
+        }
+
+        self.light[i] = light;
+        self.redraw = true;
+    }
+
+    /// Draws the chunk again, for when the brightness of all cells changes
+    pub fn retint(&mut self) {
+        self.redraw = true;
+    }
+
+    pub fn get_wall(&self, x: i32, y: i32) -> Option<ItemKind> {
+        Self::index(x, y).and_then(|i| self.walls[i])
+    }
+
+    /// Cells outside of the chunk are ignored
+    pub fn set_wall(&mut self, x: i32, y: i32, kind: Option<ItemKind>) {
+        let Some(i) = Self::index(x, y) else { return };
+
+        self.walls[i] = kind;
+        self.redraw = true;
+    }
+
+    pub fn get(&self, layer: Layer, x: i32, y: i32) -> Option<ItemKind> {
+        match layer {
+            Layer::Foreground => self.get_block(x, y),
+            Layer::Wall => self.get_wall(x, y),
+        }
+    }
+
+    pub fn set(&mut self, layer: Layer, x: i32, y: i32, kind: Option<ItemKind>) {
+        match layer {
+            Layer::Foreground => self.set_block(x, y, kind),
+            Layer::Wall => self.set_wall(x, y, kind),
+        }
+    }
+
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.get_block(x, y)
+            .is_some_and(|kind| kind.is_collidable())
+    }
+
+    /// Cells outside of the chunk count as not solid
+    fn is_exposed(&self, x: i32, y: i32) -> bool {
+        !(self.is_solid(x - 1, y)
+            && self.is_solid(x + 1, y)
+            && self.is_solid(x, y - 1)
+            && self.is_solid(x, y + 1))
+    }
+
+    fn take_dirty(&mut self) -> Vec<usize> {
+        if self.rebuild {
+            self.rebuild = false;
+            self.dirty.clear();
+            return (0..CHUNK_AREA).collect();
+        }
+
+        let mut dirty = std::mem::take(&mut self.dirty);
+        dirty.sort_unstable();
+        dirty.dedup();
+        dirty
+    }
+
+    /// Has to be called before [`ChunkData::take_dirty`], which ends the first build of the chunk
+    fn take_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.redraw) || self.rebuild
+    }
+}
+
+impl Default for ChunkData {
+    fn default() -> Self {
+        Self {
+            blocks: Box::new([None; CHUNK_AREA]),
+            walls: Box::new([None; CHUNK_AREA]),
+            levels: Box::new([0; CHUNK_AREA]),
+            light: Box::new([Light::default(); CHUNK_AREA]),
+            dirty: Vec::new(),
+            redraw: false,
+            rebuild: true,
+        }
+    }
+}
+
+impl Default for ChunkView {
+    fn default() -> Self {
+        Self {
+            block_mesh: None,
+            wall_mesh: None,
+            colliders: Box::new([None; CHUNK_AREA]),
+        }
+    }
+}
+
+// STRUCTS
+
+/// Layer of the world a block belongs to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Layer {
+    /// Blocks the player walks on and collides with
+    Foreground,
+    /// Background walls drawn behind the foreground, never collided with
+    Wall,
+}
+
+/// Quads of a chunk mesh being built, each drawing a part of the block texture atlas
+#[derive(Default)]
+struct ChunkMesh {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl ChunkMesh {
+    /// Adds a quad of [`size`] around [`center`] showing [`texture`], which is in atlas pixels
+    fn push_quad(&mut self, center: Vec2, size: Vec2, texture: Rect, color: Color) {
+        let first = self.positions.len() as u32;
+        let (min, max) = (center - size / 2., center + size / 2.);
+
+        // Texture rows go from the top down
+        self.positions.extend([
+            [min.x, min.y, 0.],
+            [max.x, min.y, 0.],
+            [max.x, max.y, 0.],
+            [min.x, max.y, 0.],
+        ]);
+        self.uvs.extend([
+            [texture.min.x, texture.max.y],
+            [texture.max.x, texture.max.y],
+            [texture.max.x, texture.min.y],
+            [texture.min.x, texture.min.y],
+        ]);
+        self.colors.extend([color.as_linear_rgba_f32(); 4]);
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    /// UVs are scaled down from the pixels of the atlas of [`atlas_size`]
+    fn build(mut self, atlas_size: Vec2) -> Mesh {
+        for uv in self.uvs.iter_mut() {
+            *uv = [uv[0] / atlas_size.x, uv[1] / atlas_size.y];
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+// EVENTS
+
+/// Sent for every cell changed through the [`WorldGrid`]
+#[derive(Event, Clone, Copy)]
+pub struct BlockUpdate {
+    pub pos: IVec2,
+    pub layer: Layer,
+}
+
+// TRAITS
+
+/// Blocks of the foreground that can be read and written cell by cell,
+/// letting generation code run on a single chunk as well as on the loaded world
+pub trait BlockGrid {
+    fn get_block(&self, x: i32, y: i32) -> Option<ItemKind>;
+
+    fn set_block(&mut self, x: i32, y: i32, kind: Option<ItemKind>);
+}
+
+impl BlockGrid for ChunkData {
+    fn get_block(&self, x: i32, y: i32) -> Option<ItemKind> {
+        self.get(Layer::Foreground, x, y)
+    }
+
+    fn set_block(&mut self, x: i32, y: i32, kind: Option<ItemKind>) {
+        self.set(Layer::Foreground, x, y, kind);
+    }
+}
+
+impl BlockGrid for WorldGrid<'_, '_> {
+    fn get_block(&self, x: i32, y: i32) -> Option<ItemKind> {
+        self.get(Layer::Foreground, IVec2::new(x, y))
+    }
+
+    fn set_block(&mut self, x: i32, y: i32, kind: Option<ItemKind>) {
+        self.set(Layer::Foreground, IVec2::new(x, y), kind);
+    }
+}
+
+// SYSTEM PARAMS
+
+/// Access to the blocks of every loaded chunk using world grid positions
+#[derive(SystemParam)]
+pub struct WorldGrid<'w, 's> {
+    chunks: Query<'w, 's, &'static mut ChunkData>,
+    loaded_chunks: Res<'w, LoadedChunks>,
+    world_save: ResMut<'w, WorldSave>,
+    updates: ResMut<'w, Events<BlockUpdate>>,
+}
+
+impl WorldGrid<'_, '_> {
+    /// Returns [`None`] for air and for cells of unloaded chunks
+    pub fn get_block(&self, pos: IVec2) -> Option<ItemKind> {
+        self.get(Layer::Foreground, pos)
+    }
+
+    /// Changes a block and records it in the world save,
+    /// returns false if the chunk of [`pos`] isn't loaded or [`pos`] is out of its height
+    pub fn set_block(&mut self, pos: IVec2, kind: Option<ItemKind>) -> bool {
+        self.set(Layer::Foreground, pos, kind)
+    }
+
+    pub fn get_wall(&self, pos: IVec2) -> Option<ItemKind> {
+        self.get(Layer::Wall, pos)
+    }
+
+    pub fn get_level(&self, pos: IVec2) -> u8 {
+        let Some(ent) = self.loaded_chunks.0.get(&ChunkPosition(chunk_of(pos.x))) else { return 0 };
+        let Ok(data) = self.chunks.get(*ent) else { return 0 };
+
+        data.get_level(pos.x - chunk_origin(chunk_of(pos.x)), pos.y)
+    }
+
+    /// Fills a cell with a fluid of the given level and records it in the world save,
+    /// returns false if the chunk of [`pos`] isn't loaded or [`pos`] is out of its height
+    pub fn set_fluid(&mut self, pos: IVec2, kind: ItemKind, level: u8) -> bool {
+        let chunk_x = chunk_of(pos.x);
+        let Some(ent) = self.loaded_chunks.0.get(&ChunkPosition(chunk_x)) else { return false };
+        let Ok(mut data) = self.chunks.get_mut(*ent) else { return false };
+
+        let local_x = pos.x - chunk_origin(chunk_x);
+        if ChunkData::index(local_x, pos.y).is_none() {
+            return false;
+        }
+
+        data.set_block(local_x, pos.y, Some(kind));
+        data.set_level(local_x, pos.y, level);
+        self.world_save.record(Layer::Foreground, pos, Some(kind));
+        self.world_save.record_level(pos, level);
+        self.updates.send(BlockUpdate {
+            pos,
+            layer: Layer::Foreground,
+        });
+
+        true
+    }
+
+    /// Returns no light for cells of unloaded chunks
+    pub fn get_light(&self, pos: IVec2) -> Light {
+        let Some(ent) = self.loaded_chunks.0.get(&ChunkPosition(chunk_of(pos.x))) else { return Light::default() };
+        let Ok(data) = self.chunks.get(*ent) else { return Light::default() };
+
+        data.get_light(pos.x - chunk_origin(chunk_of(pos.x)), pos.y)
+    }
+
+    /// Light is computed again on load so it isn't recorded in the world save
+    pub fn set_light(&mut self, pos: IVec2, light: Light) {
+        // Leaves the chunk untouched when nothing changes
+        if self.get_light(pos) == light {
+            return;
+        }
+
+        let chunk_x = chunk_of(pos.x);
+        let Some(ent) = self.loaded_chunks.0.get(&ChunkPosition(chunk_x)) else { return };
+        let Ok(mut data) = self.chunks.get_mut(*ent) else { return };
+
+        data.set_light(pos.x - chunk_origin(chunk_x), pos.y, light);
+    }
+
+    pub fn is_loaded(&self, pos: IVec2) -> bool {
+        self.loaded_chunks
+            .0
+            .contains_key(&ChunkPosition(chunk_of(pos.x)))
+    }
+
+    pub fn get(&self, layer: Layer, pos: IVec2) -> Option<ItemKind> {
+        let ent = self.loaded_chunks.0.get(&ChunkPosition(chunk_of(pos.x)))?;
+        let data = self.chunks.get(*ent).ok()?;
+
+        data.get(layer, pos.x - chunk_origin(chunk_of(pos.x)), pos.y)
+    }
+
+    /// Returns false if the chunk of [`pos`] isn't loaded or [`pos`] is out of its height
+    pub fn set(&mut self, layer: Layer, pos: IVec2, kind: Option<ItemKind>) -> bool {
+        let chunk_x = chunk_of(pos.x);
+        let Some(ent) = self.loaded_chunks.0.get(&ChunkPosition(chunk_x)) else { return false };
+        let Ok(mut data) = self.chunks.get_mut(*ent) else { return false };
+
+        let local_x = pos.x - chunk_origin(chunk_x);
+        if ChunkData::index(local_x, pos.y).is_none() {
+            return false;
+        }
+
+        data.set(layer, local_x, pos.y, kind);
+        self.world_save.record(layer, pos, kind);
+        self.updates.send(BlockUpdate { pos, layer });
+
+        true
+    }
+
+    /// Block updates sent since [`reader`] was last used, systems using the grid
+    /// can't read them with an [`EventReader`] as the grid sends them
+    pub fn read_updates(&self, reader: &mut ManualEventReader<BlockUpdate>) -> Vec<BlockUpdate> {
+        reader.iter(&self.updates).copied().collect()
+    }
+}
+
+// FUNCTIONS
+
+/// Returns the first column of the chunk at [`chunk_x`]
+pub fn chunk_origin(chunk_x: i32) -> i32 {
+    CHUNK_SIZE * chunk_x + 1
+}
+
+/// Returns the grid position of the block under [`pos`] which is in world space
+pub fn world_to_grid(pos: Vec2) -> IVec2 {
+    ((pos - WORLD_OFFSET.truncate()) / BLOCK_SIZE)
+        .round()
+        .as_ivec2()
+}
+
+/// Returns the center of the block at [`pos`] in world space
+pub fn grid_to_world(pos: IVec2) -> Vec2 {
+    pos.as_vec2() * BLOCK_SIZE + WORLD_OFFSET.truncate()
+}
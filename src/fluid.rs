@@ -0,0 +1,377 @@
+use bevy::{ecs::event::ManualEventReader, prelude::*, utils::HashSet};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    block::{BlockGraphics, BLOCK_SIZE},
+    chunk::{grid_to_world, world_to_grid, BlockUpdate, Layer, WorldGrid, CHUNK_HEIGHT},
+    health::Health,
+    item::{spawn_item, Item},
+    item_kind::ItemKind,
+    tick::{tick_timer, TickTimer},
+};
+
+// CONSTANTS
+
+/// Level of fluid sources, flowing fluids losing one level for each block they spread sideways
+pub const SOURCE_LEVEL: u8 = 8;
+/// Falling fluids spread almost as far as sources once they land
+const FALLING_LEVEL: u8 = SOURCE_LEVEL - 1;
+
+/// Game ticks between two fluid ticks, making four fluid ticks a second
+const GAME_TICKS_PER_FLUID_TICK: u32 = 5;
+
+// PLUGINS
+
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Resources
+            .insert_resource(ActiveFluidCells::default())
+            // Systems
+            .add_systems(Update, (flow_fluids.after(tick_timer), burn_in_fluids));
+    }
+}
+
+// SYSTEMS
+
+/// Steps the fluids around the cells changed by block updates every fluid tick.
+/// The changes it makes are block updates too, which keeps the fluids flowing
+fn flow_fluids(
+    mut commands: Commands,
+    mut grid: WorldGrid,
+    mut reader: Local<ManualEventReader<BlockUpdate>>,
+    mut active_cells: ResMut<ActiveFluidCells>,
+    timer: Res<TickTimer>,
+    mut game_ticks: Local<u32>,
+    mut ticks: Local<u32>,
+    block_graphics: Res<BlockGraphics>,
+) {
+    for update in grid.read_updates(&mut reader) {
+        if update.layer != Layer::Foreground {
+            continue;
+        }
+
+        for dir in [IVec2::ZERO, IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            active_cells.0.insert(update.pos + dir);
+        }
+    }
+
+    *game_ticks += timer.0.times_finished_this_tick();
+    if *game_ticks < GAME_TICKS_PER_FLUID_TICK {
+        return;
+    }
+
+    *game_ticks %= GAME_TICKS_PER_FLUID_TICK;
+
+    *ticks = ticks.wrapping_add(1);
+
+    let mut changes = Vec::new();
+    let mut waiting = Vec::new();
+
+    // Every cell is stepped from the state of the grid at the start of the tick
+    for pos in std::mem::take(&mut active_cells.0) {
+        let cell = fluid_cell(&grid, pos);
+        let next = next_cell(pos, |pos| fluid_cell(&grid, pos));
+
+        if next == cell {
+            continue;
+        }
+
+        // Slower fluids wait for their own tick
+        if let FluidCell::Fluid(kind, _) = cell {
+            if *ticks % kind.flow_interval() != 0 {
+                waiting.push(pos);
+                continue;
+            }
+        }
+
+        if let FluidCell::Fluid(kind, _) = next {
+            if *ticks % kind.flow_interval() != 0 {
+                waiting.push(pos);
+                continue;
+            }
+        }
+
+        changes.push((pos, next));
+    }
+
+    active_cells.0.extend(waiting);
+
+    for (pos, next) in changes {
+        match next {
+            FluidCell::Fluid(kind, level) => {
+                // Plants get washed away
+                if let Some(plant) = grid.get_block(pos).filter(|kind| kind.needs_support()) {
+                    spawn_item(
+                        &mut commands,
+                        plant.drop(),
+                        grid_to_world(pos),
+                        ExternalImpulse::default(),
+                        &block_graphics,
+                    );
+                }
+
+                grid.set_fluid(pos, kind, level);
+            }
+            FluidCell::Empty => {
+                grid.set_block(pos, None);
+            }
+            FluidCell::Hardened(kind) => {
+                grid.set_block(pos, Some(kind));
+            }
+            FluidCell::Solid => {}
+        }
+    }
+}
+
+/// Fluids with a contact damage hurt the bodies standing in them and burn the items
+fn burn_in_fluids(
+    mut commands: Commands,
+    grid: WorldGrid,
+    mut bodies: Query<(&GlobalTransform, &mut Health)>,
+    items: Query<(Entity, &GlobalTransform), With<Item>>,
+    time: Res<Time>,
+) {
+    let contact_damage = |kind: Option<ItemKind>| kind.map_or(0., |kind| kind.contact_damage());
+
+    for (transform, mut health) in bodies.iter_mut() {
+        let (feet, head) = body_blocks(&grid, transform.translation().truncate());
+
+        let damage = contact_damage(feet).max(contact_damage(head));
+        if damage > 0. {
+            health.damage(damage * time.delta_seconds());
+        }
+    }
+
+    for (item_ent, transform) in items.iter() {
+        let pos = world_to_grid(transform.translation().truncate());
+
+        if contact_damage(grid.get_block(pos)) > 0. {
+            commands.entity(item_ent).despawn_recursive();
+        }
+    }
+}
+
+// RESOURCES
+
+/// Cells to step on the next fluid tick
+#[derive(Resource, Default)]
+struct ActiveFluidCells(HashSet<IVec2>);
+
+// STRUCTS
+
+/// What the fluid simulation sees of a cell
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FluidCell {
+    /// Blocks fluids can't flow through, cells out of the loaded world included
+    Solid,
+    /// Air and plants, which fluids wash away
+    Empty,
+    Fluid(ItemKind, u8),
+    /// Block a fluid turned into by touching another fluid, only given by [`next_cell`]
+    Hardened(ItemKind),
+}
+
+// FUNCTIONS
+
+/// Blocks at the feet and at the head of a body centered on [`center`],
+/// bodies being about two blocks tall
+pub fn body_blocks(grid: &WorldGrid, center: Vec2) -> (Option<ItemKind>, Option<ItemKind>) {
+    let offset = Vec2::Y * BLOCK_SIZE / 2.;
+
+    (
+        grid.get_block(world_to_grid(center - offset)),
+        grid.get_block(world_to_grid(center + offset)),
+    )
+}
+
+fn fluid_cell(grid: &WorldGrid, pos: IVec2) -> FluidCell {
+    if !grid.is_loaded(pos) || !(0..CHUNK_HEIGHT).contains(&pos.y) {
+        return FluidCell::Solid;
+    }
+
+    match grid.get_block(pos) {
+        None => FluidCell::Empty,
+        Some(kind) if kind.is_fluid() => FluidCell::Fluid(kind, grid.get_level(pos)),
+        Some(kind) if kind.needs_support() => FluidCell::Empty,
+        Some(_) => FluidCell::Solid,
+    }
+}
+
+/// State of the cell at [`pos`] after the next fluid tick, [`cell`] giving the current state
+/// of any cell. Fluids touching a fluid they harden with turn into a block, sources never change
+/// otherwise, and other cells are filled by the fluid above them or else by the fullest fluid
+/// on their sides, a few levels lower
+pub fn next_cell(pos: IVec2, cell: impl Fn(IVec2) -> FluidCell) -> FluidCell {
+    let current = cell(pos);
+
+    if let FluidCell::Fluid(kind, level) = current {
+        for dir in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let FluidCell::Fluid(other, _) = cell(pos + dir) else { continue };
+
+            if let Some(hardened) = kind.harden(other, level == SOURCE_LEVEL) {
+                return FluidCell::Hardened(hardened);
+            }
+        }
+    }
+
+    if matches!(
+        current,
+        FluidCell::Solid | FluidCell::Fluid(_, SOURCE_LEVEL)
+    ) {
+        return current;
+    }
+
+    if let FluidCell::Fluid(kind, _) = cell(pos + IVec2::Y) {
+        return FluidCell::Fluid(kind, FALLING_LEVEL);
+    }
+
+    [pos - IVec2::X, pos + IVec2::X]
+        .into_iter()
+        .filter_map(|side| match cell(side) {
+            // Fluids only spread sideways once they can't fall anymore
+            FluidCell::Fluid(kind, level)
+                if level > kind.flow_drop() && cell(side - IVec2::Y) != FluidCell::Empty =>
+            {
+                Some((kind, level - kind.flow_drop()))
+            }
+            _ => None,
+        })
+        .max_by_key(|&(_, level)| level)
+        .map_or(FluidCell::Empty, |(kind, level)| {
+            FluidCell::Fluid(kind, level)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashMap;
+
+    const BASIN_WIDTH: i32 = 9;
+    const BASIN_HEIGHT: i32 = 4;
+
+    /// Cells of a basin with a solid floor at y = 0 and solid walls around it
+    struct Basin(HashMap<IVec2, FluidCell>);
+
+    impl Basin {
+        fn with(cells: &[(IVec2, FluidCell)]) -> Self {
+            Self(cells.iter().copied().collect())
+        }
+
+        fn cell(&self, pos: IVec2) -> FluidCell {
+            if !(0..BASIN_WIDTH).contains(&pos.x) || !(1..BASIN_HEIGHT).contains(&pos.y) {
+                return FluidCell::Solid;
+            }
+
+            self.0.get(&pos).copied().unwrap_or(FluidCell::Empty)
+        }
+
+        fn level(&self, x: i32, y: i32) -> u8 {
+            match self.cell(IVec2::new(x, y)) {
+                FluidCell::Fluid(_, level) => level,
+                _ => 0,
+            }
+        }
+
+        /// Steps every cell at once, like a fluid tick does
+        fn step(&mut self, ticks: usize) {
+            for _ in 0..ticks {
+                let mut next = HashMap::new();
+
+                for x in 0..BASIN_WIDTH {
+                    for y in 1..BASIN_HEIGHT {
+                        let pos = IVec2::new(x, y);
+                        let cell = next_cell(pos, |pos| self.cell(pos));
+
+                        if cell != FluidCell::Empty {
+                            next.insert(pos, cell);
+                        }
+                    }
+                }
+
+                self.0 = next;
+            }
+        }
+    }
+
+    fn water(level: u8) -> FluidCell {
+        FluidCell::Fluid(ItemKind::Water, level)
+    }
+
+    fn lava(level: u8) -> FluidCell {
+        FluidCell::Fluid(ItemKind::Lava, level)
+    }
+
+    #[test]
+    fn fluids_fall() {
+        let mut basin = Basin::with(&[(IVec2::new(4, 3), water(SOURCE_LEVEL))]);
+
+        assert_eq!(
+            next_cell(IVec2::new(4, 2), |pos| basin.cell(pos)),
+            water(FALLING_LEVEL)
+        );
+
+        basin.step(2);
+
+        assert_eq!(basin.level(4, 2), FALLING_LEVEL);
+        assert_eq!(basin.level(4, 1), FALLING_LEVEL);
+    }
+
+    #[test]
+    fn fluids_spread_sideways_losing_levels() {
+        let mut basin = Basin::with(&[(IVec2::new(4, 1), water(SOURCE_LEVEL))]);
+        basin.step(10);
+
+        for distance in 0..=4 {
+            let level = SOURCE_LEVEL - distance as u8;
+
+            assert_eq!(basin.level(4 - distance, 1), level);
+            assert_eq!(basin.level(4 + distance, 1), level);
+        }
+
+        let mut basin = Basin::with(&[(IVec2::new(4, 1), lava(SOURCE_LEVEL))]);
+        basin.step(10);
+
+        assert_eq!(basin.level(5, 1), 6);
+        assert_eq!(basin.level(6, 1), 4);
+        assert_eq!(basin.level(7, 1), 2);
+        assert_eq!(basin.level(8, 1), 0);
+    }
+
+    #[test]
+    fn fluids_drain_once_their_source_is_removed() {
+        let mut basin = Basin::with(&[(IVec2::new(4, 3), water(SOURCE_LEVEL))]);
+        basin.step(10);
+
+        basin.0.remove(&IVec2::new(4, 3));
+        basin.step(40);
+
+        assert!(basin.0.is_empty());
+    }
+
+    #[test]
+    fn lava_hardens_touching_water() {
+        let source = Basin::with(&[
+            (IVec2::new(4, 1), lava(SOURCE_LEVEL)),
+            (IVec2::new(5, 1), water(3)),
+        ]);
+
+        assert_eq!(
+            next_cell(IVec2::new(4, 1), |pos| source.cell(pos)),
+            FluidCell::Hardened(ItemKind::Obsidian)
+        );
+
+        let flowing = Basin::with(&[
+            (IVec2::new(4, 1), lava(6)),
+            (IVec2::new(4, 2), water(SOURCE_LEVEL)),
+        ]);
+
+        assert_eq!(
+            next_cell(IVec2::new(4, 1), |pos| flowing.cell(pos)),
+            FluidCell::Hardened(ItemKind::Cobblestone)
+        );
+    }
+}
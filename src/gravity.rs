@@ -0,0 +1,198 @@
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    block::{BlockGraphics, BLOCK_SIZE},
+    chunk::{grid_to_world, world_to_grid, BlockUpdate, Layer, WorldGrid},
+    item::spawn_item,
+    item_kind::ItemKind,
+};
+
+// CONSTANTS
+
+/// A bit smaller than a block so falling blocks don't catch on their neighbours
+const FALLING_BLOCK_COLLIDER_SIZE: f32 = 33.;
+
+// PLUGINS
+
+pub struct GravityPlugin;
+
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Systems
+            .add_systems(
+                Update,
+                (drop_unsupported_blocks, land_falling_blocks).chain(),
+            );
+    }
+}
+
+// SYSTEMS
+
+/// Turns the gravity blocks left without support by a block update into falling blocks,
+/// and pops off the plants left without support
+fn drop_unsupported_blocks(
+    mut commands: Commands,
+    mut grid: WorldGrid,
+    mut reader: Local<ManualEventReader<BlockUpdate>>,
+    block_graphics: Res<BlockGraphics>,
+) {
+    for update in grid.read_updates(&mut reader) {
+        if update.layer != Layer::Foreground {
+            continue;
+        }
+
+        // Placed blocks fall or pop off themselves, removed ones let the block above them go
+        for pos in [update.pos, update.pos + IVec2::Y] {
+            let Some(kind) = grid.get_block(pos) else { continue };
+
+            if kind.needs_support() && !is_rooted(&grid, pos) {
+                grid.set_block(pos, None);
+
+                spawn_item(
+                    &mut commands,
+                    kind.drop(),
+                    grid_to_world(pos),
+                    ExternalImpulse::default(),
+                    &block_graphics,
+                );
+
+                continue;
+            }
+
+            if !kind.has_gravity() || is_supported(&grid, pos) {
+                continue;
+            }
+
+            grid.set_block(pos, None);
+
+            commands.spawn((
+                FallingBlockBundle::new(kind, grid_to_world(pos), &block_graphics),
+                Name::new("Falling Block"),
+            ));
+        }
+    }
+}
+
+/// Puts falling blocks back in the grid once they reach a supported cell,
+/// crushing the plant standing there
+fn land_falling_blocks(
+    mut commands: Commands,
+    mut grid: WorldGrid,
+    falling_blocks: Query<(Entity, &FallingBlock, &Transform)>,
+    block_graphics: Res<BlockGraphics>,
+) {
+    for (falling_ent, falling_block, transform) in falling_blocks.iter() {
+        let pos = world_to_grid(transform.translation.truncate());
+
+        // The block fell out of the loaded world
+        if !grid.is_loaded(pos) || pos.y < 0 {
+            commands.entity(falling_ent).despawn_recursive();
+            continue;
+        }
+
+        if !is_supported(&grid, pos) {
+            continue;
+        }
+
+        commands.entity(falling_ent).despawn_recursive();
+
+        match grid.get_block(pos) {
+            Some(kind) if kind.needs_support() => {
+                spawn_item(
+                    &mut commands,
+                    kind.drop(),
+                    grid_to_world(pos),
+                    ExternalImpulse::default(),
+                    &block_graphics,
+                );
+            }
+            // Something was built in the way, the block drops as an item instead
+            Some(kind) if !kind.is_fluid() => {
+                spawn_item(
+                    &mut commands,
+                    falling_block.0,
+                    grid_to_world(pos),
+                    ExternalImpulse::default(),
+                    &block_graphics,
+                );
+
+                continue;
+            }
+            _ => {}
+        }
+
+        grid.set_block(pos, Some(falling_block.0));
+    }
+}
+
+// COMPONENTS
+
+/// Gravity block on its way down, holding the kind it lands as
+#[derive(Component)]
+pub struct FallingBlock(pub ItemKind);
+
+// BUNDLES
+
+#[derive(Bundle)]
+struct FallingBlockBundle {
+    // rendering
+    sprite: TextureAtlasSprite,
+    texture_atlas: Handle<TextureAtlas>,
+
+    // physics
+    rigid_body: RigidBody,
+    collider: Collider,
+    locked_axes: LockedAxes,
+    velocity: Velocity,
+
+    // game related
+    falling_block: FallingBlock,
+
+    // required
+    spatial_bundle: SpatialBundle,
+}
+
+impl FallingBlockBundle {
+    fn new(kind: ItemKind, translation: Vec2, block_graphics: &BlockGraphics) -> Self {
+        Self {
+            sprite: TextureAtlasSprite {
+                index: kind.to_index(),
+                custom_size: Some(Vec2::splat(BLOCK_SIZE)),
+                ..default()
+            },
+            texture_atlas: block_graphics.atlas_handle.clone(),
+            rigid_body: RigidBody::Dynamic,
+            collider: Collider::cuboid(FALLING_BLOCK_COLLIDER_SIZE, FALLING_BLOCK_COLLIDER_SIZE),
+            locked_axes: LockedAxes::ROTATION_LOCKED | LockedAxes::TRANSLATION_LOCKED_X,
+            velocity: Velocity::default(),
+            falling_block: FallingBlock(kind),
+            spatial_bundle: SpatialBundle {
+                transform: Transform::from_xyz(translation.x, translation.y, 0.),
+                ..default()
+            },
+        }
+    }
+}
+
+// FUNCTIONS
+
+/// Blocks rest on anything but air, fluids and plants.
+/// Cells above unloaded chunks count as supported
+fn is_supported(grid: &WorldGrid, pos: IVec2) -> bool {
+    let below = pos - IVec2::Y;
+
+    !grid.is_loaded(below)
+        || grid
+            .get_block(below)
+            .is_some_and(|kind| !kind.is_fluid() && !kind.needs_support())
+}
+
+/// Plants stand on anything but air and fluids, plants included.
+/// Cells above unloaded chunks count as supported
+fn is_rooted(grid: &WorldGrid, pos: IVec2) -> bool {
+    let below = pos - IVec2::Y;
+
+    !grid.is_loaded(below) || grid.get_block(below).is_some_and(|kind| !kind.is_fluid())
+}
@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+
+// PLUGINS
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Reflection
+            .register_type::<Health>();
+    }
+}
+
+// COMPONENTS
+
+/// Health of anything that can get hurt, dead once it reaches 0
+#[derive(Component, Reflect)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.);
+    }
+
+    pub fn heal_fully(&mut self) {
+        self.current = self.max;
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.
+    }
+}
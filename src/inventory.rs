@@ -11,6 +11,15 @@ const INVENTORY_SIZE: usize = 36;
 const HOTBAR_SIZE: usize = 9;
 const STACK_SIZE: usize = 5;
 
+/// Items the player spawns with
+const STARTING_ITEMS: [ItemKind; 5] = [
+    ItemKind::Bucket,
+    ItemKind::Torch,
+    ItemKind::Torch,
+    ItemKind::Torch,
+    ItemKind::Torch,
+];
+
 const UI_HOTBAR_BOTTOM_SPACING: f32 = 10.;
 
 const UI_HOTBAR_SIZE_MUTL: f32 = 2.;
@@ -45,7 +54,7 @@ impl Plugin for InventoryPlugin {
             .insert_resource(IsInventoryOpen::default())
             // Systems
             .add_systems(PreStartup, load_assets)
-            .add_systems(Startup, spawn_ui)
+            .add_systems(Startup, (spawn_ui, give_starting_items))
             .add_systems(
                 Update,
                 (
@@ -72,6 +81,12 @@ fn load_assets(mut assets: ResMut<UiAssets>, asset_server: Res<AssetServer>) {
     assets.font = asset_server.load(FONT_NAME);
 }
 
+fn give_starting_items(mut inventory: ResMut<Inv>) {
+    for kind in STARTING_ITEMS {
+        inventory.add(kind, || {});
+    }
+}
+
 fn spawn_ui(mut commands: Commands, ui_assets: Res<UiAssets>, block_graphics: Res<BlockGraphics>) {
     // Spawn inventory holder
     commands
@@ -680,6 +695,18 @@ impl<const I: usize, const H: usize, const S: usize> Inventory<I, H, S> {
         }
     }
 
+    /// Takes one item from the slot under the cursor and gives [`kind`] back,
+    /// in the same slot if it got emptied
+    pub fn replace_at_cursor(&mut self, kind: ItemKind) {
+        self.remove_at_cursor();
+
+        if self.current_hotbar_slot().is_none() {
+            *self.current_hotbar_slot_mut() = Some(InventorySlot { kind, quantity: 1 });
+        } else {
+            self.add(kind, || {});
+        }
+    }
+
     pub fn current_hotbar_slot(&self) -> &Option<InventorySlot> {
         &self.items[self.hotbar_cursor]
     }
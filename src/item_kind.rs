@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use serde::Deserialize;
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum BlockSoundType {
@@ -10,7 +11,7 @@ pub enum BlockSoundType {
     Wood,
 }
 
-#[derive(Component, Default, PartialEq, Eq, Clone, Copy, Debug, Reflect)]
+#[derive(Component, Default, PartialEq, Eq, Clone, Copy, Debug, Deserialize, Reflect)]
 pub enum ItemKind {
     #[default]
     // row 1
@@ -37,6 +38,14 @@ pub enum ItemKind {
     RedSandstone,
     Cactus,
     RedTulip,
+    Granite,
+    Diorite,
+    Andesite,
+    Gravel,
+    Tuff,
+    Water,
+    Clay,
+    Chest,
     // row 3
     CoalOre,
     CoalOreDeepslate,
@@ -54,15 +63,251 @@ pub enum ItemKind {
     EmraldOreDeepslate,
     DiamondOre,
     DiamondOreDeepslate,
+    // row 4
+    BirchLog,
+    BirchLeaves,
+    SpruceLog,
+    SpruceLeaves,
+    JungleLog,
+    JungleLeaves,
+    TallGrass,
+    DeadBush,
+    Dandelion,
+    Bucket,
+    WaterBucket,
+    Lava,
+    Obsidian,
+    LavaBucket,
+    Torch,
 }
 
 impl ItemKind {
+    /// Every kind, in declaration order
+    pub const ALL: [ItemKind; 61] = [
+        // row 1
+        ItemKind::Dirt,
+        ItemKind::Grass,
+        ItemKind::Stone,
+        ItemKind::Cobblestone,
+        ItemKind::Deepslate,
+        ItemKind::CobbledDeepslate,
+        ItemKind::Bedrock,
+        ItemKind::HayBale,
+        ItemKind::OakLog,
+        ItemKind::LeafedOakLog,
+        ItemKind::OakPlank,
+        ItemKind::Leaves,
+        ItemKind::OakSapling,
+        ItemKind::CraftingTable,
+        ItemKind::Furnace,
+        ItemKind::FurnaceBurning,
+        // row 2
+        ItemKind::Sand,
+        ItemKind::Sandstone,
+        ItemKind::RedSand,
+        ItemKind::RedSandstone,
+        ItemKind::Cactus,
+        ItemKind::RedTulip,
+        ItemKind::Granite,
+        ItemKind::Diorite,
+        ItemKind::Andesite,
+        ItemKind::Gravel,
+        ItemKind::Tuff,
+        ItemKind::Water,
+        ItemKind::Clay,
+        ItemKind::Chest,
+        // row 3
+        ItemKind::CoalOre,
+        ItemKind::CoalOreDeepslate,
+        ItemKind::CopperOre,
+        ItemKind::CopperOreDeepslate,
+        ItemKind::IronOre,
+        ItemKind::IronOreDeepslate,
+        ItemKind::GoldOre,
+        ItemKind::GoldOreDeepslate,
+        ItemKind::LapisOre,
+        ItemKind::LapisOreDeepslate,
+        ItemKind::RedstoneOre,
+        ItemKind::RedstoneOreDeepslate,
+        ItemKind::EmraldOre,
+        ItemKind::EmraldOreDeepslate,
+        ItemKind::DiamondOre,
+        ItemKind::DiamondOreDeepslate,
+        // row 4
+        ItemKind::BirchLog,
+        ItemKind::BirchLeaves,
+        ItemKind::SpruceLog,
+        ItemKind::SpruceLeaves,
+        ItemKind::JungleLog,
+        ItemKind::JungleLeaves,
+        ItemKind::TallGrass,
+        ItemKind::DeadBush,
+        ItemKind::Dandelion,
+        ItemKind::Bucket,
+        ItemKind::WaterBucket,
+        ItemKind::Lava,
+        ItemKind::Obsidian,
+        ItemKind::LavaBucket,
+        ItemKind::Torch,
+    ];
+
     pub fn is_block(&self) -> bool {
+        use ItemKind::*;
         match self {
+            Bucket | WaterBucket | LavaBucket => false,
             _ => true,
         }
     }
 
+    /// Plants and tree parts can be walked through
+    pub fn is_collidable(&self) -> bool {
+        use ItemKind::*;
+        !matches!(
+            *self,
+            OakLog
+                | LeafedOakLog
+                | Leaves
+                | OakSapling
+                | Cactus
+                | RedTulip
+                | Water
+                | Lava
+                | BirchLog
+                | BirchLeaves
+                | SpruceLog
+                | SpruceLeaves
+                | JungleLog
+                | JungleLeaves
+                | TallGrass
+                | DeadBush
+                | Dandelion
+                | Torch
+        )
+    }
+
+    /// Falls when the block under it is removed
+    pub fn has_gravity(&self) -> bool {
+        matches!(*self, ItemKind::Sand | ItemKind::RedSand | ItemKind::Gravel)
+    }
+
+    /// Plants pop off when the block under them is broken
+    pub fn needs_support(&self) -> bool {
+        use ItemKind::*;
+        matches!(
+            *self,
+            OakSapling | Cactus | RedTulip | TallGrass | DeadBush | Dandelion | Torch
+        )
+    }
+
+    /// Fluids can't be broken and get replaced by placed blocks
+    pub fn is_fluid(&self) -> bool {
+        matches!(*self, ItemKind::Water | ItemKind::Lava)
+    }
+
+    /// Fluid ticks between two steps of the fluid
+    pub fn flow_interval(&self) -> u32 {
+        match *self {
+            ItemKind::Lava => 3,
+            _ => 1,
+        }
+    }
+
+    /// Levels the fluid loses for each block it spreads sideways
+    pub fn flow_drop(&self) -> u8 {
+        match *self {
+            ItemKind::Lava => 2,
+            _ => 1,
+        }
+    }
+
+    /// Block the fluid turns into when touching the [`other`] fluid
+    pub fn harden(&self, other: Self, source: bool) -> Option<Self> {
+        use ItemKind::*;
+        match (*self, other) {
+            (Lava, Water) if source => Some(Obsidian),
+            (Lava, Water) => Some(Cobblestone),
+            _ => None,
+        }
+    }
+
+    /// Health lost every second standing in the block
+    pub fn contact_damage(&self) -> f32 {
+        match *self {
+            ItemKind::Lava => 8.,
+            _ => 0.,
+        }
+    }
+
+    /// Light the block gives off, from 0 to 15
+    pub fn light_emission(&self) -> u8 {
+        match *self {
+            ItemKind::Lava => 15,
+            ItemKind::Torch => 14,
+            ItemKind::FurnaceBurning => 13,
+            _ => 0,
+        }
+    }
+
+    /// Light can't go through solid blocks, which still get lit themselves
+    pub fn is_opaque(&self) -> bool {
+        self.is_collidable()
+    }
+
+    /// Light lost going through the block on top of the usual level per block
+    pub fn light_filter(&self) -> u8 {
+        use ItemKind::*;
+        match *self {
+            Water => 2,
+            Leaves | BirchLeaves | SpruceLeaves | JungleLeaves => 1,
+            _ => 0,
+        }
+    }
+
+    /// Fluid poured out of a filled bucket
+    pub fn bucket_fluid(&self) -> Option<Self> {
+        match *self {
+            ItemKind::WaterBucket => Some(ItemKind::Water),
+            ItemKind::LavaBucket => Some(ItemKind::Lava),
+            _ => None,
+        }
+    }
+
+    /// Bucket filled by scooping up the fluid
+    pub fn filled_bucket(&self) -> Option<Self> {
+        match *self {
+            ItemKind::Water => Some(ItemKind::WaterBucket),
+            ItemKind::Lava => Some(ItemKind::LavaBucket),
+            _ => None,
+        }
+    }
+
+    /// Kind the block turns into when generated inside the deepslate layer
+    pub fn deepslate_variant(&self) -> Self {
+        use ItemKind::*;
+        match *self {
+            Stone => Deepslate,
+            CoalOre => CoalOreDeepslate,
+            CopperOre => CopperOreDeepslate,
+            IronOre => IronOreDeepslate,
+            GoldOre => GoldOreDeepslate,
+            LapisOre => LapisOreDeepslate,
+            RedstoneOre => RedstoneOreDeepslate,
+            EmraldOre => EmraldOreDeepslate,
+            DiamondOre => DiamondOreDeepslate,
+            kind => kind,
+        }
+    }
+
+    /// Item dropped when the block is broken
+    pub fn drop(&self) -> Self {
+        use ItemKind::*;
+        match *self {
+            Stone => Cobblestone,
+            Deepslate => CobbledDeepslate,
+            kind => kind,
+        }
+    }
+
     pub fn get_sound_type(&self) -> Option<BlockSoundType> {
         if !self.is_block() {
             return None;
@@ -70,16 +315,24 @@ impl ItemKind {
 
         use ItemKind::*;
         match *self {
-            OakSapling | RedTulip | Cactus => Some(BlockSoundType::Cloth),
-            Dirt | Grass | HayBale | Leaves => Some(BlockSoundType::Dirt),
+            OakSapling | RedTulip | Cactus | TallGrass | DeadBush | Dandelion => {
+                Some(BlockSoundType::Cloth)
+            }
+            Dirt | Grass | HayBale | Leaves | Clay | BirchLeaves | SpruceLeaves | JungleLeaves => {
+                Some(BlockSoundType::Dirt)
+            }
             Sand | RedSand => Some(BlockSoundType::Sand),
+            Gravel => Some(BlockSoundType::Gravel),
             Stone | Cobblestone | Deepslate | CobbledDeepslate | Bedrock | Furnace
             | FurnaceBurning | Sandstone | RedSandstone | CoalOre | CoalOreDeepslate
             | CopperOre | CopperOreDeepslate | IronOre | IronOreDeepslate | GoldOre
             | GoldOreDeepslate | LapisOre | LapisOreDeepslate | RedstoneOre
             | RedstoneOreDeepslate | EmraldOre | EmraldOreDeepslate | DiamondOre
-            | DiamondOreDeepslate => Some(BlockSoundType::Stone),
-            OakLog | LeafedOakLog | OakPlank | CraftingTable => Some(BlockSoundType::Wood),
+            | DiamondOreDeepslate | Granite | Diorite | Andesite | Tuff | Obsidian => {
+                Some(BlockSoundType::Stone)
+            }
+            OakLog | LeafedOakLog | OakPlank | CraftingTable | Chest | BirchLog | SpruceLog
+            | JungleLog | Torch => Some(BlockSoundType::Wood),
             _ => None,
         }
     }
@@ -111,6 +364,14 @@ impl ItemKind {
             RedSandstone => 19,
             Cactus => 20,
             RedTulip => 21,
+            Granite => 22,
+            Diorite => 23,
+            Andesite => 24,
+            Gravel => 25,
+            Tuff => 26,
+            Water => 27,
+            Clay => 28,
+            Chest => 29,
             // row 3
             CoalOre => 32,
             CoalOreDeepslate => 33,
@@ -128,6 +389,109 @@ impl ItemKind {
             EmraldOreDeepslate => 45,
             DiamondOre => 46,
             DiamondOreDeepslate => 47,
+            // row 4
+            BirchLog => 48,
+            BirchLeaves => 49,
+            SpruceLog => 50,
+            SpruceLeaves => 51,
+            JungleLog => 52,
+            JungleLeaves => 53,
+            TallGrass => 54,
+            DeadBush => 55,
+            Dandelion => 56,
+            Bucket => 57,
+            WaterBucket => 58,
+            Lava => 59,
+            Obsidian => 60,
+            LavaBucket => 61,
+            Torch => 62,
+        }
+    }
+
+    /// Number the kind is written as in save files, which must never change once given out so
+    /// saves don't depend on the texture atlas. They are the atlas slots the kinds had when saves
+    /// started storing them, new kinds taking the next free number
+    pub fn save_id(&self) -> u16 {
+        use ItemKind::*;
+        match *self {
+            Dirt => 0,
+            Grass => 1,
+            Stone => 2,
+            Cobblestone => 3,
+            Deepslate => 4,
+            CobbledDeepslate => 5,
+            Bedrock => 6,
+            HayBale => 7,
+            OakLog => 8,
+            LeafedOakLog => 9,
+            OakPlank => 10,
+            Leaves => 11,
+            OakSapling => 12,
+            CraftingTable => 13,
+            Furnace => 14,
+            FurnaceBurning => 15,
+            Sand => 16,
+            Sandstone => 17,
+            RedSand => 18,
+            RedSandstone => 19,
+            Cactus => 20,
+            RedTulip => 21,
+            Granite => 22,
+            Diorite => 23,
+            Andesite => 24,
+            Gravel => 25,
+            Tuff => 26,
+            Water => 27,
+            Clay => 28,
+            Chest => 29,
+            CoalOre => 32,
+            CoalOreDeepslate => 33,
+            CopperOre => 34,
+            CopperOreDeepslate => 35,
+            IronOre => 36,
+            IronOreDeepslate => 37,
+            GoldOre => 38,
+            GoldOreDeepslate => 39,
+            LapisOre => 40,
+            LapisOreDeepslate => 41,
+            RedstoneOre => 42,
+            RedstoneOreDeepslate => 43,
+            EmraldOre => 44,
+            EmraldOreDeepslate => 45,
+            DiamondOre => 46,
+            DiamondOreDeepslate => 47,
+            BirchLog => 48,
+            BirchLeaves => 49,
+            SpruceLog => 50,
+            SpruceLeaves => 51,
+            JungleLog => 52,
+            JungleLeaves => 53,
+            TallGrass => 54,
+            DeadBush => 55,
+            Dandelion => 56,
+            Bucket => 57,
+            WaterBucket => 58,
+            Lava => 59,
+            Obsidian => 60,
+            LavaBucket => 61,
+            Torch => 62,
+        }
+    }
+
+    /// Inverse of [`ItemKind::save_id`]
+    pub fn from_save_id(id: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.save_id() == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_ids_read_back_as_the_same_kind() {
+        for kind in ItemKind::ALL {
+            assert_eq!(ItemKind::from_save_id(kind.save_id()), Some(kind));
         }
     }
 }
@@ -0,0 +1,319 @@
+use bevy::{ecs::event::ManualEventReader, prelude::*};
+use std::collections::VecDeque;
+
+use crate::{
+    chunk::{chunk_origin, render_chunks, BlockUpdate, ChunkData, Layer, WorldGrid, CHUNK_HEIGHT},
+    item_kind::ItemKind,
+    world::{Chunk, ChunkPosition, CHUNK_SIZE},
+};
+
+// CONSTANTS
+
+/// Light of the open sky and of the brightest blocks, light losing one level for each block it
+/// spreads so a block change can't change the light further than that
+pub const MAX_LIGHT: u8 = 15;
+/// Unlit cells are drawn this bright so caves aren't completely black
+const MIN_BRIGHTNESS: f32 = 0.04;
+
+// PLUGINS
+
+pub struct LightPlugin;
+
+impl Plugin for LightPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Resources
+            .insert_resource(Daylight(1.))
+            // Systems
+            .add_systems(
+                PostUpdate,
+                (update_light, retint_chunks).before(render_chunks),
+            );
+    }
+}
+
+// SYSTEMS
+
+/// Relights the cells a block update can reach and the cells around newly loaded chunks,
+/// before the chunks get drawn
+fn update_light(
+    mut grid: WorldGrid,
+    mut reader: Local<ManualEventReader<BlockUpdate>>,
+    new_chunks: Query<&ChunkPosition, Added<Chunk>>,
+) {
+    let reach = MAX_LIGHT as i32;
+
+    // Sunlight can reach the whole column under a changed block
+    let mut areas: Vec<LightArea> = grid
+        .read_updates(&mut reader)
+        .into_iter()
+        .filter(|update| update.layer == Layer::Foreground)
+        .map(|update| LightArea {
+            min_x: update.pos.x - reach,
+            max_x: update.pos.x + reach,
+            max_y: update.pos.y + reach,
+        })
+        .collect();
+
+    areas.extend(new_chunks.iter().map(|chunk_pos| {
+        let origin = chunk_origin(chunk_pos.0);
+
+        LightArea {
+            min_x: origin - reach,
+            max_x: origin + CHUNK_SIZE - 1 + reach,
+            max_y: CHUNK_HEIGHT - 1,
+        }
+    }));
+
+    for area in merge_areas(areas) {
+        relight(&mut grid, area);
+    }
+}
+
+/// Draws every chunk again when the daylight changes, as the brightness of all cells does
+fn retint_chunks(daylight: Res<Daylight>, mut chunks: Query<&mut ChunkData>) {
+    if !daylight.is_changed() {
+        return;
+    }
+
+    for mut data in chunks.iter_mut() {
+        data.retint();
+    }
+}
+
+// RESOURCES
+
+/// Fraction of the sunlight reaching the world, sky light being scaled by it
+#[derive(Resource, PartialEq)]
+pub struct Daylight(pub f32);
+
+// STRUCTS
+
+/// Light of a cell, sunlight and the light of blocks being kept apart
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Light {
+    pub sky: u8,
+    pub block: u8,
+}
+
+impl Light {
+    /// Color multiplier of the blocks and walls drawn in the cell
+    pub fn brightness(&self, daylight: f32) -> f32 {
+        let light = (self.sky as f32 * daylight).max(self.block as f32) / MAX_LIGHT as f32;
+
+        MIN_BRIGHTNESS + (1. - MIN_BRIGHTNESS) * light
+    }
+}
+
+/// Columns from [`min_x`] to [`max_x`] relit from the ground up to [`max_y`]
+#[derive(Clone, Copy)]
+struct LightArea {
+    min_x: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+/// Cells of a [`LightArea`] being relit, columns of unloaded chunks left out
+struct LightRegion {
+    min_x: i32,
+    width: i32,
+    height: i32,
+    loaded: Vec<bool>,
+    blocks: Vec<Option<ItemKind>>,
+}
+
+impl LightRegion {
+    fn new(grid: &WorldGrid, area: LightArea) -> Self {
+        let width = area.max_x - area.min_x + 1;
+        let height = area.max_y.clamp(0, CHUNK_HEIGHT - 1) + 1;
+
+        Self {
+            min_x: area.min_x,
+            width,
+            height,
+            loaded: (area.min_x..=area.max_x)
+                .map(|x| grid.is_loaded(IVec2::new(x, 0)))
+                .collect(),
+            blocks: vec![None; (width * height) as usize],
+        }
+    }
+
+    /// Returns [`None`] for cells outside of the region
+    fn index(&self, pos: IVec2) -> Option<usize> {
+        let x = pos.x - self.min_x;
+
+        if !(0..self.width).contains(&x)
+            || !(0..self.height).contains(&pos.y)
+            || !self.loaded[x as usize]
+        {
+            return None;
+        }
+
+        Some((x * self.height + pos.y) as usize)
+    }
+
+    fn positions(&self) -> impl Iterator<Item = IVec2> + '_ {
+        (0..self.width)
+            .filter(|&x| self.loaded[x as usize])
+            .flat_map(move |x| (0..self.height).map(move |y| IVec2::new(self.min_x + x, y)))
+    }
+
+    /// Cells right outside of the region, which the light can come in from
+    fn border(&self) -> impl Iterator<Item = IVec2> + '_ {
+        let sides = (0..self.height).flat_map(move |y| {
+            [
+                IVec2::new(self.min_x - 1, y),
+                IVec2::new(self.min_x + self.width, y),
+            ]
+        });
+        let top = (0..self.width).map(move |x| IVec2::new(self.min_x + x, self.height));
+
+        sides.chain(top)
+    }
+}
+
+// FUNCTIONS
+
+/// Joins the overlapping areas so no cell gets relit twice
+fn merge_areas(mut areas: Vec<LightArea>) -> Vec<LightArea> {
+    areas.sort_unstable_by_key(|area| area.min_x);
+
+    let mut merged: Vec<LightArea> = Vec::new();
+
+    for area in areas {
+        match merged.last_mut() {
+            Some(last) if area.min_x <= last.max_x + 1 => {
+                last.max_x = last.max_x.max(area.max_x);
+                last.max_y = last.max_y.max(area.max_y);
+            }
+            _ => merged.push(area),
+        }
+    }
+
+    merged
+}
+
+/// Computes the light of every cell of the area again from the sky, the blocks giving off light
+/// and the light coming in from around it, which can't have changed
+fn relight(grid: &mut WorldGrid, area: LightArea) {
+    let mut region = LightRegion::new(grid, area);
+    let mut sky = vec![0; region.blocks.len()];
+    let mut block = vec![0; region.blocks.len()];
+
+    for x in area.min_x..=area.max_x {
+        if !grid.is_loaded(IVec2::new(x, 0)) {
+            continue;
+        }
+
+        // Sunlight falls straight down without fading until something stops it
+        let mut sunlight = MAX_LIGHT;
+
+        for y in (0..CHUNK_HEIGHT).rev() {
+            let pos = IVec2::new(x, y);
+            let kind = grid.get_block(pos);
+            let cell_sky = sunlight.saturating_sub(kind.map_or(0, |kind| kind.light_filter()));
+
+            if kind.is_some_and(|kind| kind.is_opaque()) {
+                sunlight = 0;
+            } else {
+                sunlight = cell_sky;
+            }
+
+            let Some(i) = region.index(pos) else { continue };
+
+            region.blocks[i] = kind;
+            sky[i] = cell_sky;
+            block[i] = kind.map_or(0, |kind| kind.light_emission());
+        }
+    }
+
+    let border: Vec<(IVec2, Light)> = region
+        .border()
+        .map(|pos| {
+            let kind = grid.get_block(pos);
+            let mut light = grid.get_light(pos);
+
+            if stops_light(kind, false) {
+                light.sky = 0;
+            }
+
+            if stops_light(kind, true) {
+                light.block = 0;
+            }
+
+            (pos, light)
+        })
+        .collect();
+
+    spread_light(
+        &region,
+        &mut sky,
+        border.iter().map(|&(pos, light)| (pos, light.sky)),
+        false,
+    );
+    spread_light(
+        &region,
+        &mut block,
+        border.iter().map(|&(pos, light)| (pos, light.block)),
+        true,
+    );
+
+    for pos in region.positions() {
+        let Some(i) = region.index(pos) else { continue };
+
+        grid.set_light(
+            pos,
+            Light {
+                sky: sky[i],
+                block: block[i],
+            },
+        );
+    }
+}
+
+/// Opaque blocks get lit but stop the light, apart from the block light they give off themselves
+fn stops_light(kind: Option<ItemKind>, block_light: bool) -> bool {
+    kind.is_some_and(|kind| kind.is_opaque() && !(block_light && kind.light_emission() > 0))
+}
+
+/// Floods the light of one channel through the region, starting from its lit cells
+/// and from the light of the [`border`] cells
+fn spread_light(
+    region: &LightRegion,
+    light: &mut [u8],
+    border: impl Iterator<Item = (IVec2, u8)>,
+    block_light: bool,
+) {
+    let mut queue: VecDeque<(IVec2, u8)> = region
+        .positions()
+        .filter_map(|pos| {
+            let value = light[region.index(pos)?];
+            (value > 0).then_some((pos, value))
+        })
+        .chain(border.filter(|&(_, value)| value > 1))
+        .collect();
+
+    while let Some((pos, value)) = queue.pop_front() {
+        if let Some(i) = region.index(pos) {
+            // The cell got brighter since, or stops the light
+            if light[i] != value || stops_light(region.blocks[i], block_light) {
+                continue;
+            }
+        }
+
+        for dir in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let next = pos + dir;
+            let Some(i) = region.index(next) else { continue };
+
+            let filter = region.blocks[i]
+                .filter(|kind| !kind.is_opaque())
+                .map_or(0, |kind| kind.light_filter());
+            let received = value.saturating_sub(1 + filter);
+
+            if received > light[i] {
+                light[i] = received;
+                queue.push_back((next, received));
+            }
+        }
+    }
+}
@@ -4,18 +4,36 @@ use bevy_kira_audio::prelude::*;
 use bevy_rapier2d::prelude::*;
 use block::BlockPlugin;
 use camera::CamPlugin;
+use chunk::ChunkPlugin;
+use fluid::FluidPlugin;
 use gamemode::GameModePlugin;
+use gravity::GravityPlugin;
+use health::HealthPlugin;
 use inventory::InventoryPlugin;
+use light::LightPlugin;
 use player::PlayerPlugin;
+use save::SavePlugin;
+use structure::StructurePlugin;
+use tick::TickPlugin;
+use time::WorldTimePlugin;
 use world::WorldPlugin;
 
 mod block;
 mod camera;
+mod chunk;
+mod fluid;
 mod gamemode;
+mod gravity;
+mod health;
 mod inventory;
 mod item;
 mod item_kind;
+mod light;
 mod player;
+mod save;
+mod structure;
+mod tick;
+mod time;
 mod utils;
 mod world;
 
@@ -32,23 +50,40 @@ fn main() {
                         ..default()
                     }),
                     ..default()
+                })
+                .set(AssetPlugin {
+                    // Hot reloads the world settings
+                    watch_for_changes: bevy::asset::ChangeWatcher::with_delay(
+                        std::time::Duration::from_millis(200),
+                    ),
+                    ..default()
                 }),
             RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0),
             // RapierDebugRenderPlugin::default(),
             AudioPlugin,
             WorldInspectorPlugin::new(),
             // EditorPlugin::default(),
+        ))
+        .add_plugins((
             PlayerPlugin,
             WorldPlugin,
+            ChunkPlugin,
+            StructurePlugin,
+            TickPlugin,
+            GravityPlugin,
+            FluidPlugin,
+            HealthPlugin,
             BlockPlugin,
             InventoryPlugin,
             GameModePlugin,
+            SavePlugin,
             CamPlugin,
+            LightPlugin,
+            WorldTimePlugin,
         ))
         .insert_resource(RapierConfiguration {
             gravity: Vec2::new(0., -1000.),
             ..default()
         })
-        .insert_resource(ClearColor(Color::rgba(126. / 255., 200. / 255., 1., 1.)))
         .run();
 }
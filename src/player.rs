@@ -7,13 +7,15 @@ use bevy_rapier2d::{prelude::*, rapier::prelude::CollisionEventFlags};
 use std::f32::consts::PI;
 
 use crate::{
-    block::{Block, BlockBundle, BlockGraphics, BLOCK_SIZE},
+    block::{Block, BlockGraphics, BLOCK_SIZE},
     camera::MainCamera,
+    chunk::{grid_to_world, world_to_grid, Layer, WorldGrid},
+    fluid::{body_blocks, SOURCE_LEVEL},
+    health::Health,
     inventory::{Inv, IsInventoryOpen},
     item::{spawn_item, Item, ItemSensor},
     item_kind::{BlockSoundType, ItemKind},
     utils::{in_reach, leans_to_left, leans_to_right, map},
-    world::{Chunk, ChunkPosition, PlayerChunkPosition, World},
 };
 
 // CONSTANTS
@@ -42,6 +44,29 @@ const BACK_LEG_Z_INDEX: f32 = 0.;
 
 const PLAYER_REACH: f32 = 3.;
 
+const PLAYER_SPAWN: Vec2 = Vec2::new(BLOCK_SIZE, 60. * BLOCK_SIZE);
+const PLAYER_MAX_HEALTH: f32 = 20.;
+
+/// Seconds the player can stay with its head in a fluid before drowning
+const PLAYER_MAX_BREATH: f32 = 10.;
+/// Breath got back every second out of fluids
+const BREATH_REFILL_RATE: f32 = 5.;
+/// Health lost every second once out of breath
+const DROWNING_DAMAGE: f32 = 2.;
+
+const SWIM_GRAVITY_SCALE: f32 = 0.3;
+const SWIM_DAMPING: f32 = 3.;
+const SWIM_SPEED_MULT: f32 = 0.5;
+const SWIM_UP_SPEED: f32 = 150.;
+
+const UI_BREATH_METER_WIDTH: f32 = 160.;
+const UI_BREATH_METER_HEIGHT: f32 = 6.;
+const UI_BREATH_METER_BOTTOM_SPACING: f32 = 62.;
+const UI_BREATH_METER_COLOR: Color = Color::rgb(0.35, 0.6, 1.);
+
+/// Held to place and break walls instead of blocks
+const WALL_MODIFIER: KeyCode = KeyCode::ControlLeft;
+
 // PLUGINS
 
 pub struct PlayerPlugin;
@@ -57,7 +82,10 @@ impl Plugin for PlayerPlugin {
             .insert_resource(LastPlayerPosition::default())
             // Systems
             .add_systems(PreStartup, (load_player_graphics, load_audio))
-            .add_systems(Startup, (spawn_player, spawn_block_selector))
+            .add_systems(
+                Startup,
+                (spawn_player, spawn_block_selector, spawn_breath_meter),
+            )
             .add_systems(FixedUpdate, player_controller_movement)
             .add_systems(
                 Update,
@@ -72,6 +100,10 @@ impl Plugin for PlayerPlugin {
                     place_block,
                     break_block,
                     pick_up_item,
+                    update_submersion,
+                    breathe,
+                    update_breath_meter,
+                    respawn_dead_player,
                 ),
             )
             // Reflection
@@ -88,6 +120,8 @@ impl Plugin for PlayerPlugin {
             .register_type::<Jump>()
             .register_type::<Direction>()
             .register_type::<WaveIndex>()
+            .register_type::<Submerged>()
+            .register_type::<Breath>()
             .register_type::<BlockSelector>();
     }
 }
@@ -170,7 +204,7 @@ fn spawn_block_selector(mut commands: Commands, asset_server: Res<AssetServer>)
 }
 
 fn player_controller_movement(
-    mut player_query: Query<(&Speed, &mut Velocity, &GlobalTransform)>,
+    mut player_query: Query<(&Speed, &Submerged, &mut Velocity, &GlobalTransform)>,
     keys: Res<Input<KeyCode>>,
     rapier_context: Res<RapierContext>,
     is_inventory_open: Res<IsInventoryOpen>,
@@ -179,18 +213,33 @@ fn player_controller_movement(
         return;
     }
 
-    for (speed, mut rb_vel, gtr) in player_query.iter_mut() {
+    for (speed, submerged, mut rb_vel, gtr) in player_query.iter_mut() {
         let left = keys.any_pressed([KeyCode::A, KeyCode::Left]);
         let right = keys.any_pressed([KeyCode::D, KeyCode::Right]);
         let jump = keys.just_pressed(KeyCode::Space);
+        let swim_up = keys.pressed(KeyCode::Space);
         let running = keys.pressed(KeyCode::ShiftLeft);
 
         let x_axis = -(left as i8) + right as i8;
         let move_delta_x = x_axis as f32;
         let sp = if running { speed.1 } else { speed.0 };
+        let sp = if submerged.body {
+            sp * SWIM_SPEED_MULT
+        } else {
+            sp
+        };
 
         rb_vel.linvel.x = move_delta_x * sp;
 
+        // Swimming up takes the place of jumping in fluids
+        if submerged.body {
+            if swim_up {
+                rb_vel.linvel.y = rb_vel.linvel.y.max(SWIM_UP_SPEED);
+            }
+
+            continue;
+        }
+
         if jump {
             let ray_pos = vec2(gtr.translation().x, gtr.translation().y - 79.);
             let ray_dir = Vec2::new(0., -20.);
@@ -484,7 +533,7 @@ fn change_graphics_with_direction(
 }
 
 fn select_block(
-    blocks: Query<(&GlobalTransform, Entity), (With<Block>, Without<BlockSelector>)>,
+    grid: WorldGrid,
     player: Query<&GlobalTransform, With<Player>>,
     mut selected_block: ResMut<SelectedBlock>,
     mut last_cur_pos: ResMut<LastCursorPosition>,
@@ -504,10 +553,8 @@ fn select_block(
             .cursor_position()
             .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor)) else { return };
 
-    let pos = vec2(
-        (cursor_position.x / BLOCK_SIZE).round() * BLOCK_SIZE,
-        (cursor_position.y / BLOCK_SIZE).round() * BLOCK_SIZE,
-    );
+    let block_pos = world_to_grid(cursor_position);
+    let pos = grid_to_world(block_pos);
 
     let player_transform = player.single().translation();
     let player_transform = vec2(player_transform.x, player_transform.y);
@@ -525,11 +572,8 @@ fn select_block(
         return;
     }
 
-    for (block_transform, block_ent) in blocks.iter() {
-        if block_transform.translation().x == pos.x && block_transform.translation().y == pos.y {
-            selected_block.0 = Some(block_ent);
-            break;
-        }
+    if is_occupied(&grid, block_pos) || grid.get_wall(block_pos).is_some() {
+        selected_block.0 = Some(block_pos);
     }
 }
 
@@ -553,15 +597,11 @@ fn highlight_selected_block(
 }
 
 fn place_block(
-    mut commands: Commands,
-    world: Query<&GlobalTransform, With<World>>,
-    chunks: Query<(Entity, &ChunkPosition), With<Chunk>>,
-    blocks: Query<&GlobalTransform, With<Block>>,
+    mut grid: WorldGrid,
     player_transform: Query<&GlobalTransform, With<Player>>,
-    current_chunk: Res<PlayerChunkPosition>,
     mut inventory: ResMut<Inv>,
-    blocks_graphics: Res<BlockGraphics>,
     mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
     window: Query<&Window, With<PrimaryWindow>>,
     camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     is_inventory_open: Res<IsInventoryOpen>,
@@ -579,58 +619,67 @@ fn place_block(
 
     let player_transform = player_transform.single().translation();
     let player_transform = vec2(player_transform.x, player_transform.y);
-    let block_pos = (cursor_position / BLOCK_SIZE).round() * BLOCK_SIZE;
+    let block_pos = world_to_grid(cursor_position);
 
     if !(mouse.just_pressed(MouseButton::Right)
-        && in_reach(player_transform, block_pos, PLAYER_REACH, BLOCK_SIZE))
+        && in_reach(
+            player_transform,
+            grid_to_world(block_pos),
+            PLAYER_REACH,
+            BLOCK_SIZE,
+        ))
     {
         return;
     }
 
-    if inventory.current_hotbar_slot().is_none() {
-        return;
-    }
+    let Some(slot) = inventory.current_hotbar_slot() else { return };
+    let block_kind = slot.kind;
+    let layer = held_layer(&keys);
 
-    if blocks
-        .iter()
-        .any(|&b| b.translation().x == block_pos.x && b.translation().y == block_pos.y)
-    {
-        return;
-    }
+    // Empty buckets scoop up fluid sources
+    if block_kind == ItemKind::Bucket {
+        let scooped = grid
+            .get_block(block_pos)
+            .and_then(|kind| kind.filled_bucket());
+        let Some(filled_bucket) = scooped else { return };
 
-    let block_kind = inventory.current_hotbar_slot().unwrap().kind;
-    inventory.remove_at_cursor();
+        if grid.get_level(block_pos) == SOURCE_LEVEL && grid.set_block(block_pos, None) {
+            inventory.replace_at_cursor(filled_bucket);
+        }
 
-    let world_transform = world.single().translation();
+        return;
+    }
 
-    let chunk_ent = {
-        let mut ent = None;
-        for (chunk_ent, chunk_pos) in chunks.iter() {
-            if chunk_pos.0 == current_chunk.0 {
-                ent = Some(chunk_ent);
-                break;
-            }
+    // Filled buckets pour a source out on the foreground
+    if let Some(fluid) = block_kind.bucket_fluid() {
+        if !is_occupied(&grid, block_pos) && grid.set_block(block_pos, Some(fluid)) {
+            inventory.replace_at_cursor(ItemKind::Bucket);
         }
-        ent.unwrap()
-    };
 
-    let spawn_pos = vec2(
-        block_pos.x - world_transform.x,
-        block_pos.y - world_transform.y,
-    );
+        return;
+    }
 
-    let block_ent = commands
-        .spawn(BlockBundle::new(block_kind, spawn_pos, &blocks_graphics))
-        .id();
+    if !block_kind.is_block() {
+        return;
+    }
+
+    // Fluids get replaced and walls can't be reached through blocks
+    if is_occupied(&grid, block_pos) || (layer == Layer::Wall && grid.get_wall(block_pos).is_some())
+    {
+        return;
+    }
 
-    commands.entity(chunk_ent).add_child(block_ent);
+    if grid.set(layer, block_pos, Some(block_kind)) {
+        inventory.remove_at_cursor();
+    }
 }
 
 fn break_block(
     mut commands: Commands,
-    blocks: Query<(&GlobalTransform, Entity, &ItemKind), With<Block>>,
+    mut grid: WorldGrid,
     player_transform: Query<&GlobalTransform, With<Player>>,
     mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
     window: Query<&Window, With<PrimaryWindow>>,
     camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     blocks_graphics: Res<BlockGraphics>,
@@ -655,52 +704,60 @@ fn break_block(
         return;
     }
 
-    let block_pos = (cursor_position / BLOCK_SIZE).round() * BLOCK_SIZE;
+    let block_pos = world_to_grid(cursor_position);
+    let translation = grid_to_world(block_pos);
 
-    for (block_transform, block_ent, block_kind) in blocks.iter() {
-        let block_transform = block_transform.translation();
+    if !in_reach(
+        vec2(player_transform.x, player_transform.y),
+        translation,
+        PLAYER_REACH,
+        BLOCK_SIZE,
+    ) {
+        return;
+    }
 
-        if block_transform.x == block_pos.x
-            && block_transform.y == block_pos.y
-            && in_reach(
-                vec2(player_transform.x, player_transform.y),
-                block_pos,
-                PLAYER_REACH,
-                BLOCK_SIZE,
-            )
-        {
-            // Break block
+    let layer = held_layer(&keys);
 
-            let translation = vec2(block_transform.x, block_transform.y);
-            let ext_impulse = ExternalImpulse {
-                impulse: vec2(0., 50.),
-                ..default()
-            };
-
-            if let Some(block_type) = block_kind.get_sound_type() {
-                let audio_handle = match block_type {
-                    BlockSoundType::Cloth => player_audio.break_cloth.clone(),
-                    BlockSoundType::Dirt => player_audio.break_dirt.clone(),
-                    BlockSoundType::Gravel => player_audio.break_gravel.clone(),
-                    BlockSoundType::Sand => player_audio.break_sand.clone(),
-                    BlockSoundType::Stone => player_audio.break_stone.clone(),
-                    BlockSoundType::Wood => player_audio.break_wood.clone(),
-                };
-
-                audio.play(audio_handle);
-            }
+    // Walls can't be reached through blocks
+    if layer == Layer::Wall && is_occupied(&grid, block_pos) {
+        return;
+    }
 
-            spawn_item(
-                &mut commands,
-                *block_kind,
-                translation,
-                ext_impulse,
-                &blocks_graphics,
-            );
-            commands.entity(block_ent).despawn_recursive();
-            return;
-        }
+    let Some(block_kind) = grid.get(layer, block_pos) else { return };
+
+    if block_kind.is_fluid() {
+        return;
+    }
+
+    // Break block
+
+    grid.set(layer, block_pos, None);
+
+    let ext_impulse = ExternalImpulse {
+        impulse: vec2(0., 50.),
+        ..default()
+    };
+
+    if let Some(block_type) = block_kind.get_sound_type() {
+        let audio_handle = match block_type {
+            BlockSoundType::Cloth => player_audio.break_cloth.clone(),
+            BlockSoundType::Dirt => player_audio.break_dirt.clone(),
+            BlockSoundType::Gravel => player_audio.break_gravel.clone(),
+            BlockSoundType::Sand => player_audio.break_sand.clone(),
+            BlockSoundType::Stone => player_audio.break_stone.clone(),
+            BlockSoundType::Wood => player_audio.break_wood.clone(),
+        };
+
+        audio.play(audio_handle);
     }
+
+    spawn_item(
+        &mut commands,
+        block_kind.drop(),
+        translation,
+        ext_impulse,
+        &blocks_graphics,
+    );
 }
 
 fn pick_up_item(
@@ -734,6 +791,107 @@ fn pick_up_item(
     }
 }
 
+/// Checks which parts of the player are in a fluid,
+/// fluids carrying the player up and slowing it down
+fn update_submersion(
+    grid: WorldGrid,
+    mut player: Query<
+        (
+            &GlobalTransform,
+            &mut Submerged,
+            &mut GravityScale,
+            &mut Damping,
+        ),
+        With<Player>,
+    >,
+) {
+    let (gtr, mut submerged, mut gravity_scale, mut damping) = player.single_mut();
+
+    let (feet, head) = body_blocks(&grid, gtr.translation().truncate());
+    let body = feet.is_some_and(|kind| kind.is_fluid());
+    let head = head.is_some_and(|kind| kind.is_fluid());
+
+    if submerged.body == body && submerged.head == head {
+        return;
+    }
+
+    *submerged = Submerged { body, head };
+    gravity_scale.0 = if body { SWIM_GRAVITY_SCALE } else { 1. };
+    damping.linear_damping = if body { SWIM_DAMPING } else { 0. };
+}
+
+/// Breath runs out while the head is in a fluid, then the player starts drowning
+fn breathe(
+    mut player: Query<(&Submerged, &mut Breath, &mut Health), With<Player>>,
+    time: Res<Time>,
+) {
+    let (submerged, mut breath, mut health) = player.single_mut();
+    let delta = time.delta_seconds();
+
+    if !submerged.head {
+        breath.current = (breath.current + BREATH_REFILL_RATE * delta).min(breath.max);
+        return;
+    }
+
+    breath.current = (breath.current - delta).max(0.);
+
+    if breath.current == 0. {
+        health.damage(DROWNING_DAMAGE * delta);
+    }
+}
+
+fn spawn_breath_meter(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Breath Meter"),
+        BreathMeter,
+        NodeBundle {
+            style: Style {
+                width: Val::Px(UI_BREATH_METER_WIDTH),
+                height: Val::Px(UI_BREATH_METER_HEIGHT),
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(UI_BREATH_METER_BOTTOM_SPACING),
+                left: Val::Percent(50.),
+                margin: UiRect::left(Val::Px(-UI_BREATH_METER_WIDTH / 2.)),
+                ..default()
+            },
+            background_color: UI_BREATH_METER_COLOR.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+}
+
+/// The meter shrinks with the breath left and hides once it's full
+fn update_breath_meter(
+    breath: Query<&Breath, (With<Player>, Changed<Breath>)>,
+    mut meter: Query<(&mut Style, &mut Visibility), With<BreathMeter>>,
+) {
+    let Ok(breath) = breath.get_single() else { return };
+    let (mut style, mut visibility) = meter.single_mut();
+
+    style.width = Val::Px(UI_BREATH_METER_WIDTH * breath.current / breath.max);
+    *visibility = if breath.current < breath.max {
+        Visibility::Visible
+    } else {
+        Visibility::Hidden
+    };
+}
+
+fn respawn_dead_player(
+    mut player: Query<(&mut Transform, &mut Velocity, &mut Health, &mut Breath), With<Player>>,
+) {
+    let (mut transform, mut velocity, mut health, mut breath) = player.single_mut();
+
+    if !health.is_dead() {
+        return;
+    }
+
+    transform.translation = PLAYER_SPAWN.extend(PLAYER_Z_INDEX);
+    *velocity = Velocity::zero();
+    health.heal_fully();
+    breath.current = breath.max;
+}
+
 // RESOURCES
 
 #[derive(Resource)]
@@ -763,7 +921,7 @@ struct PlayerAudio {
 }
 
 #[derive(Resource, Default)]
-struct SelectedBlock(Option<Entity>);
+struct SelectedBlock(Option<IVec2>);
 
 #[derive(Resource, Default)]
 struct LastCursorPosition(Vec2);
@@ -839,6 +997,23 @@ enum Direction {
 #[derive(Component, Reflect)]
 struct WaveIndex(f32);
 
+/// Parts of the player that are in a fluid
+#[derive(Component, Default, Reflect)]
+struct Submerged {
+    body: bool,
+    head: bool,
+}
+
+/// Seconds of air left
+#[derive(Component, Reflect)]
+struct Breath {
+    current: f32,
+    max: f32,
+}
+
+#[derive(Component)]
+struct BreathMeter;
+
 #[derive(Component, Reflect)]
 struct BlockSelector;
 
@@ -850,6 +1025,9 @@ struct PlayerBundle {
     speed: Speed,
     jump: Jump,
     direction: Direction,
+    health: Health,
+    breath: Breath,
+    submerged: Submerged,
 
     // colliders
     collider: Collider,
@@ -864,6 +1042,8 @@ struct PlayerBundle {
     velocity: Velocity,
     ext_impulse: ExternalImpulse,
     locked_axes: LockedAxes,
+    gravity_scale: GravityScale,
+    damping: Damping,
 
     // required
     spatial_bundle: SpatialBundle,
@@ -905,6 +1085,12 @@ impl PlayerBundle {
             speed: Speed(walking_speed, runnign_speed),
             jump: Jump(jump_force),
             direction: default(),
+            health: Health::new(PLAYER_MAX_HEALTH),
+            breath: Breath {
+                current: PLAYER_MAX_BREATH,
+                max: PLAYER_MAX_BREATH,
+            },
+            submerged: default(),
             collider,
             collider_mass: ColliderMassProperties::Mass(mass),
             player: Player,
@@ -916,6 +1102,8 @@ impl PlayerBundle {
             velocity: default(),
             ext_impulse: default(),
             locked_axes: LockedAxes::ROTATION_LOCKED,
+            gravity_scale: default(),
+            damping: default(),
             spatial_bundle: default(),
         }
     }
@@ -1053,6 +1241,12 @@ impl Default for PlayerBundle {
             speed: Speed(300., 500.),
             jump: Jump(100.),
             direction: default(),
+            health: Health::new(PLAYER_MAX_HEALTH),
+            breath: Breath {
+                current: PLAYER_MAX_BREATH,
+                max: PLAYER_MAX_BREATH,
+            },
+            submerged: default(),
             collider: Collider::capsule_y(60., 8.),
             // collider: Collider::cuboid(10., 76.),
             // collider: Collider::round_cuboid(10., 76., 0.03),
@@ -1066,8 +1260,10 @@ impl Default for PlayerBundle {
             velocity: default(),
             ext_impulse: default(),
             locked_axes: LockedAxes::ROTATION_LOCKED,
+            gravity_scale: default(),
+            damping: default(),
             spatial_bundle: SpatialBundle {
-                transform: Transform::from_xyz(BLOCK_SIZE, 60. * BLOCK_SIZE, PLAYER_Z_INDEX),
+                transform: Transform::from_translation(PLAYER_SPAWN.extend(PLAYER_Z_INDEX)),
                 ..default()
             },
         }
@@ -1085,3 +1281,19 @@ impl Default for PlayerGraphicsHolderBundle {
         }
     }
 }
+
+// FUNCTIONS
+
+/// Layer the player is currently building on
+fn held_layer(keys: &Input<KeyCode>) -> Layer {
+    if keys.pressed(WALL_MODIFIER) {
+        Layer::Wall
+    } else {
+        Layer::Foreground
+    }
+}
+
+/// Fluids don't count as they can be built into
+fn is_occupied(grid: &WorldGrid, pos: IVec2) -> bool {
+    grid.get_block(pos).is_some_and(|kind| !kind.is_fluid())
+}
@@ -0,0 +1,430 @@
+use bevy::{app::AppExit, prelude::*, utils::HashMap};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    chunk::Layer,
+    fluid::SOURCE_LEVEL,
+    item_kind::ItemKind,
+    world::{chunk_of, ChunkPosition},
+};
+
+// CONSTANTS
+
+const SAVE_PATH: &str = "saves/world.sav";
+const SAVE_MAGIC: &[u8; 4] = b"MC2D";
+const SAVE_VERSION: u32 = 3;
+/// Saves from before walls, every block of them being on the foreground
+const SAVE_VERSION_NO_LAYERS: u32 = 1;
+/// Saves from before flowing fluids, every fluid of them being a source
+const SAVE_VERSION_NO_LEVELS: u32 = 2;
+
+/// Written in place of a kind id for blocks that were removed
+const REMOVED_BLOCK: u16 = u16::MAX;
+
+const AUTOSAVE_INTERVAL: f32 = 30.;
+
+// PLUGINS
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Resources
+            .insert_resource(WorldSave::default())
+            .insert_resource(AutosaveTimer(Timer::from_seconds(
+                AUTOSAVE_INTERVAL,
+                TimerMode::Repeating,
+            )))
+            // Systems
+            .add_systems(Startup, load_world_save)
+            .add_systems(Update, autosave_world)
+            .add_systems(Last, save_world_on_exit);
+    }
+}
+
+// SYSTEMS
+
+fn load_world_save(mut world_save: ResMut<WorldSave>) {
+    match WorldSave::read_from(SAVE_PATH) {
+        Ok(save) => *world_save = save,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => error!("Could not load world save {}: {}", SAVE_PATH, err),
+    }
+}
+
+fn autosave_world(
+    mut world_save: ResMut<WorldSave>,
+    mut timer: ResMut<AutosaveTimer>,
+    time: Res<Time>,
+) {
+    if !timer.0.tick(time.delta()).just_finished() || !world_save.dirty {
+        return;
+    }
+
+    write_world_save(&mut world_save);
+}
+
+fn save_world_on_exit(mut world_save: ResMut<WorldSave>, exit: EventReader<AppExit>) {
+    if exit.is_empty() || !world_save.dirty {
+        return;
+    }
+
+    write_world_save(&mut world_save);
+}
+
+fn write_world_save(world_save: &mut WorldSave) {
+    match world_save.write_to(SAVE_PATH) {
+        Ok(()) => world_save.dirty = false,
+        Err(err) => error!("Could not write world save {}: {}", SAVE_PATH, err),
+    }
+}
+
+// RESOURCES
+
+/// Every change made to the generated world, grouped by chunk
+/// so it can be replayed when the chunk is generated again
+#[derive(Resource, Default)]
+pub struct WorldSave {
+    chunks: HashMap<ChunkPosition, ChunkDiff>,
+    dirty: bool,
+}
+
+#[derive(Resource)]
+struct AutosaveTimer(Timer);
+
+// STRUCTS
+
+/// Block overrides of a single chunk
+#[derive(Default)]
+pub struct ChunkDiff {
+    /// [`None`] meaning the block was removed
+    pub blocks: HashMap<(Layer, IVec2), Option<ItemKind>>,
+    /// Levels of the fluids placed in the chunk that aren't sources
+    pub levels: HashMap<IVec2, u8>,
+}
+
+impl WorldSave {
+    pub fn record(&mut self, layer: Layer, pos: IVec2, kind: Option<ItemKind>) {
+        let diff = self
+            .chunks
+            .entry(ChunkPosition(chunk_of(pos.x)))
+            .or_default();
+
+        diff.blocks.insert((layer, pos), kind);
+
+        // Placed blocks start over as sources or non fluids
+        if layer == Layer::Foreground {
+            diff.levels.remove(&pos);
+        }
+
+        self.dirty = true;
+    }
+
+    pub fn record_level(&mut self, pos: IVec2, level: u8) {
+        let diff = self
+            .chunks
+            .entry(ChunkPosition(chunk_of(pos.x)))
+            .or_default();
+
+        if level == SOURCE_LEVEL {
+            diff.levels.remove(&pos);
+        } else {
+            diff.levels.insert(pos, level);
+        }
+
+        self.dirty = true;
+    }
+
+    pub fn chunk(&self, chunk_pos: &ChunkPosition) -> Option<&ChunkDiff> {
+        self.chunks.get(chunk_pos)
+    }
+
+    /// Layout (little endian):
+    /// magic, version: u32, chunk count: u32,
+    /// then for each chunk: x: i32, block count: u32,
+    /// then for each block: layer: u8, x: i32, y: i32, kind id: u16,
+    /// then level count: u32, then for each level: x: i32, y: i32, level: u8
+    fn write_to(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Write next to the save first so a crash can't leave it half written
+        let tmp_path = path.with_extension("tmp");
+        let mut w = BufWriter::new(File::create(&tmp_path)?);
+
+        w.write_all(SAVE_MAGIC)?;
+        w.write_all(&SAVE_VERSION.to_le_bytes())?;
+        w.write_all(&(self.chunks.len() as u32).to_le_bytes())?;
+
+        for (chunk_pos, diff) in self.chunks.iter() {
+            w.write_all(&chunk_pos.0.to_le_bytes())?;
+            w.write_all(&(diff.blocks.len() as u32).to_le_bytes())?;
+
+            for ((layer, pos), kind) in diff.blocks.iter() {
+                let id = match kind {
+                    Some(kind) => kind.save_id(),
+                    None => REMOVED_BLOCK,
+                };
+
+                w.write_all(&[layer_to_byte(*layer)])?;
+                w.write_all(&pos.x.to_le_bytes())?;
+                w.write_all(&pos.y.to_le_bytes())?;
+                w.write_all(&id.to_le_bytes())?;
+            }
+
+            w.write_all(&(diff.levels.len() as u32).to_le_bytes())?;
+
+            for (pos, level) in diff.levels.iter() {
+                w.write_all(&pos.x.to_le_bytes())?;
+                w.write_all(&pos.y.to_le_bytes())?;
+                w.write_all(&[*level])?;
+            }
+        }
+
+        w.flush()?;
+        drop(w);
+
+        fs::rename(tmp_path, path)
+    }
+
+    fn read_from(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != SAVE_MAGIC {
+            return Err(invalid_data("not a world save"));
+        }
+
+        let version = read_u32(&mut r)?;
+        if ![SAVE_VERSION, SAVE_VERSION_NO_LAYERS, SAVE_VERSION_NO_LEVELS].contains(&version) {
+            return Err(invalid_data(format!(
+                "unsupported save version {}",
+                version
+            )));
+        }
+
+        let mut chunks = HashMap::new();
+        for _ in 0..read_u32(&mut r)? {
+            let chunk_x = read_i32(&mut r)?;
+            let mut diff = ChunkDiff::default();
+
+            for _ in 0..read_u32(&mut r)? {
+                let layer = match version {
+                    SAVE_VERSION_NO_LAYERS => Layer::Foreground,
+                    _ => {
+                        let byte = read_u8(&mut r)?;
+                        layer_from_byte(byte)
+                            .ok_or_else(|| invalid_data(format!("unknown layer {}", byte)))?
+                    }
+                };
+                let pos = IVec2::new(read_i32(&mut r)?, read_i32(&mut r)?);
+                let kind = match read_u16(&mut r)? {
+                    REMOVED_BLOCK => None,
+                    id => Some(ItemKind::from_save_id(id).ok_or_else(|| {
+                        invalid_data(format!("unknown block id {} at {}", id, pos))
+                    })?),
+                };
+
+                diff.blocks.insert((layer, pos), kind);
+            }
+
+            if version == SAVE_VERSION {
+                for _ in 0..read_u32(&mut r)? {
+                    let pos = IVec2::new(read_i32(&mut r)?, read_i32(&mut r)?);
+                    diff.levels.insert(pos, read_u8(&mut r)?);
+                }
+            }
+
+            chunks.insert(ChunkPosition(chunk_x), diff);
+        }
+
+        Ok(Self {
+            chunks,
+            dirty: false,
+        })
+    }
+}
+
+// FUNCTIONS
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn layer_to_byte(layer: Layer) -> u8 {
+    match layer {
+        Layer::Foreground => 0,
+        Layer::Wall => 1,
+    }
+}
+
+fn layer_from_byte(byte: u8) -> Option<Layer> {
+    match byte {
+        0 => Some(Layer::Foreground),
+        1 => Some(Layer::Wall),
+        _ => None,
+    }
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16(r: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_i32(r: &mut impl Read) -> io::Result<i32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Save file only used by the test called [`name`]
+    fn test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("minecraft-2d-{}-{}.sav", name, std::process::id()))
+    }
+
+    /// Reads [`bytes`] back as a save file
+    fn read_bytes(name: &str, bytes: &[u8]) -> WorldSave {
+        let path = test_path(name);
+        fs::write(&path, bytes).unwrap();
+        let save = WorldSave::read_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        save
+    }
+
+    /// Start of a save file holding a single chunk with [`block_count`] blocks
+    fn header(version: u32, chunk_x: i32, block_count: u32) -> Vec<u8> {
+        let mut bytes = SAVE_MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(chunk_x.to_le_bytes());
+        bytes.extend(block_count.to_le_bytes());
+        bytes
+    }
+
+    fn block(bytes: &mut Vec<u8>, layer: Option<Layer>, pos: IVec2, id: u16) {
+        if let Some(layer) = layer {
+            bytes.push(layer_to_byte(layer));
+        }
+
+        bytes.extend(pos.x.to_le_bytes());
+        bytes.extend(pos.y.to_le_bytes());
+        bytes.extend(id.to_le_bytes());
+    }
+
+    #[test]
+    fn written_saves_read_back_the_same() {
+        let mut save = WorldSave::default();
+        save.record(Layer::Foreground, IVec2::new(3, 40), Some(ItemKind::Torch));
+        save.record(Layer::Wall, IVec2::new(3, 40), Some(ItemKind::OakPlank));
+        save.record(Layer::Foreground, IVec2::new(-20, 12), None);
+        save.record(Layer::Foreground, IVec2::new(5, 30), Some(ItemKind::Water));
+        save.record_level(IVec2::new(5, 30), 3);
+
+        let path = test_path("round-trip");
+        save.write_to(&path).unwrap();
+        let read = WorldSave::read_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(!read.dirty);
+        assert_eq!(read.chunks.len(), save.chunks.len());
+
+        for (chunk_pos, diff) in save.chunks.iter() {
+            let read_diff = read.chunk(chunk_pos).unwrap();
+            assert_eq!(read_diff.blocks, diff.blocks);
+            assert_eq!(read_diff.levels, diff.levels);
+        }
+    }
+
+    #[test]
+    fn version_1_blocks_are_on_the_foreground() {
+        let mut bytes = header(SAVE_VERSION_NO_LAYERS, 0, 2);
+        block(
+            &mut bytes,
+            None,
+            IVec2::new(3, 40),
+            ItemKind::Sand.save_id(),
+        );
+        block(&mut bytes, None, IVec2::new(4, 40), REMOVED_BLOCK);
+
+        let save = read_bytes("v1", &bytes);
+        let diff = save.chunk(&ChunkPosition(0)).unwrap();
+
+        assert_eq!(diff.blocks.len(), 2);
+        assert_eq!(
+            diff.blocks.get(&(Layer::Foreground, IVec2::new(3, 40))),
+            Some(&Some(ItemKind::Sand))
+        );
+        assert_eq!(
+            diff.blocks.get(&(Layer::Foreground, IVec2::new(4, 40))),
+            Some(&None)
+        );
+        assert!(diff.levels.is_empty());
+    }
+
+    #[test]
+    fn version_2_fluids_are_sources() {
+        let mut bytes = header(SAVE_VERSION_NO_LEVELS, -1, 2);
+        let wall = ItemKind::Cobblestone.save_id();
+        block(&mut bytes, Some(Layer::Wall), IVec2::new(-5, 20), wall);
+        let water = ItemKind::Water.save_id();
+        block(
+            &mut bytes,
+            Some(Layer::Foreground),
+            IVec2::new(-6, 30),
+            water,
+        );
+
+        let save = read_bytes("v2", &bytes);
+        let diff = save.chunk(&ChunkPosition(-1)).unwrap();
+
+        assert_eq!(
+            diff.blocks.get(&(Layer::Wall, IVec2::new(-5, 20))),
+            Some(&Some(ItemKind::Cobblestone))
+        );
+        assert_eq!(
+            diff.blocks.get(&(Layer::Foreground, IVec2::new(-6, 30))),
+            Some(&Some(ItemKind::Water))
+        );
+        assert!(diff.levels.is_empty());
+    }
+
+    #[test]
+    fn unknown_block_ids_are_rejected() {
+        let mut bytes = header(SAVE_VERSION, 0, 1);
+        block(&mut bytes, Some(Layer::Foreground), IVec2::new(3, 40), 30);
+        bytes.extend(0u32.to_le_bytes());
+
+        let path = test_path("unknown-id");
+        fs::write(&path, &bytes).unwrap();
+        let err = WorldSave::read_from(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,184 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadState, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::{BoxedFuture, HashMap},
+};
+use serde::Deserialize;
+
+use crate::{chunk::ChunkData, item_kind::ItemKind};
+
+// CONSTANTS
+
+const STRUCTURES_PATH: &str = "structures";
+
+// PLUGINS
+
+pub struct StructurePlugin;
+
+impl Plugin for StructurePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Resources
+            .insert_resource(StructureHandles::default())
+            // Assets
+            .add_asset::<Structure>()
+            .init_asset_loader::<StructureLoader>()
+            // Systems
+            .add_systems(Startup, load_structures)
+            .add_systems(Update, apply_structures);
+    }
+}
+
+// SYSTEMS
+
+fn load_structures(
+    mut structure_handles: ResMut<StructureHandles>,
+    asset_server: Res<AssetServer>,
+) {
+    match asset_server.load_folder(STRUCTURES_PATH) {
+        Ok(handles) => structure_handles.0 = handles,
+        Err(err) => error!(
+            "Could not load structures from {}: {}",
+            STRUCTURES_PATH, err
+        ),
+    }
+}
+
+/// Hands the structures to world generation once none of them is loading anymore,
+/// and again every time one of them is modified
+fn apply_structures(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<Structure>>,
+    structure_assets: Res<Assets<Structure>>,
+    structure_handles: Res<StructureHandles>,
+    structures: Option<Res<Structures>>,
+    asset_server: Res<AssetServer>,
+) {
+    if structures.is_some() && asset_events.is_empty() {
+        return;
+    }
+
+    asset_events.clear();
+
+    if structure_handles
+        .0
+        .iter()
+        .any(|handle| asset_server.get_load_state(handle.id()) == LoadState::Loading)
+    {
+        return;
+    }
+
+    let mut structures = structure_assets
+        .iter()
+        .map(|(_, structure)| structure.clone())
+        .collect::<Vec<_>>();
+
+    // Placement goes through the structures in order, which has to be the same on every run
+    structures.sort_by(|a, b| a.name.cmp(&b.name));
+
+    commands.insert_resource(Structures(structures));
+}
+
+// RESOURCES
+
+#[derive(Resource, Default)]
+struct StructureHandles(Vec<HandleUntyped>);
+
+/// Every loaded structure, sorted by name
+#[derive(Resource, Default)]
+pub struct Structures(pub Vec<Structure>);
+
+// STRUCTS
+
+/// Template of blocks placed by world generation, loaded from a `.structure.ron` file
+#[derive(Clone, Deserialize, TypeUuid, TypePath)]
+#[uuid = "0b7d4e52-93c1-4f6a-8d2e-6a41c5f9b3d7"]
+pub struct Structure {
+    pub name: String,
+    pub placement: StructurePlacement,
+    /// Names of the biomes the structure can be found in, every biome if empty
+    #[serde(default)]
+    pub biomes: Vec<String>,
+    /// One chunk in this many gets the structure
+    pub rarity: u32,
+    /// Block each character of the rows stands for, [`None`] clearing the cell.
+    /// Characters missing from it leave the cell untouched
+    pub palette: HashMap<char, Option<ItemKind>>,
+    /// Rows of the template from top to bottom
+    pub rows: Vec<String>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum StructurePlacement {
+    /// Stands on the ground with its [`sink`] bottom rows buried
+    Surface { sink: i32 },
+    /// Buried with its bottom row between the bounds
+    Underground {
+        below: Option<i32>,
+        above: Option<i32>,
+    },
+}
+
+impl Structure {
+    pub fn width(&self) -> i32 {
+        self.rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0) as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.rows.len() as i32
+    }
+
+    /// Places the structure with its bottom left corner at [`corner`], in chunk space.
+    /// Blocks falling outside of the chunk are dropped
+    pub fn place(&self, corner: IVec2, data: &mut ChunkData) {
+        for (i, row) in self.rows.iter().enumerate() {
+            let y = corner.y + self.height() - 1 - i as i32;
+
+            for (j, c) in row.chars().enumerate() {
+                if let Some(&kind) = self.palette.get(&c) {
+                    data.set_block(corner.x + j as i32, y, kind);
+                }
+            }
+        }
+    }
+}
+
+// ASSET LOADERS
+
+#[derive(Default)]
+struct StructureLoader;
+
+impl AssetLoader for StructureLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display();
+
+            let structure = ron::de::from_bytes::<Structure>(bytes).map_err(|err| {
+                bevy::asset::Error::msg(format!("Invalid structure in {}: {}", path, err))
+            })?;
+
+            if structure.rarity == 0 {
+                return Err(bevy::asset::Error::msg(format!(
+                    "Invalid structure in {}: rarity can't be 0",
+                    path
+                )));
+            }
+
+            load_context.set_default_asset(LoadedAsset::new(structure));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["structure.ron"]
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    chunk::{chunk_origin, ChunkData, CHUNK_HEIGHT},
+    item_kind::ItemKind,
+    world::{ChunkPosition, CHUNK_SIZE},
+};
+
+// CONSTANTS
+
+const TICKS_PER_SECOND: f32 = 20.;
+/// Blocks of each loaded chunk randomly ticked every game tick
+const RANDOM_TICKS_PER_CHUNK: usize = 3;
+
+// PLUGINS
+
+pub struct TickPlugin;
+
+impl Plugin for TickPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Resources
+            .insert_resource(TickTimer(Timer::from_seconds(
+                1. / TICKS_PER_SECOND,
+                TimerMode::Repeating,
+            )))
+            // Events
+            .add_event::<RandomTick>()
+            // Systems
+            .add_systems(Update, (tick_timer, random_tick).chain());
+    }
+}
+
+// SYSTEMS
+
+/// Advances the game ticks, systems running on them reading the [`TickTimer`] after this one
+pub fn tick_timer(mut timer: ResMut<TickTimer>, time: Res<Time>) {
+    timer.0.tick(time.delta());
+}
+
+/// Picks a few random cells of every loaded chunk each game tick,
+/// sending a [`RandomTick`] for the ones holding a block
+fn random_tick(
+    timer: Res<TickTimer>,
+    mut random_ticks: EventWriter<RandomTick>,
+    chunks: Query<(&ChunkPosition, &ChunkData)>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..timer.0.times_finished_this_tick() {
+        for (chunk_pos, data) in chunks.iter() {
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let x = rng.gen_range(0..CHUNK_SIZE);
+                let y = rng.gen_range(0..CHUNK_HEIGHT);
+
+                let Some(kind) = data.get_block(x, y) else { continue };
+
+                random_ticks.send(RandomTick {
+                    pos: IVec2::new(chunk_origin(chunk_pos.0) + x, y),
+                    kind,
+                });
+            }
+        }
+    }
+}
+
+// RESOURCES
+
+#[derive(Resource)]
+pub struct TickTimer(pub Timer);
+
+// EVENTS
+
+/// Slowly drives the blocks that change on their own, like growing saplings.
+/// Listeners should check the block is still there as the event can be a frame old
+#[derive(Event, Clone, Copy)]
+pub struct RandomTick {
+    pub pos: IVec2,
+    pub kind: ItemKind,
+}
@@ -0,0 +1,218 @@
+use bevy::prelude::*;
+use std::f32::consts::{PI, TAU};
+
+use crate::{
+    camera::MainCamera,
+    light::Daylight,
+    tick::{tick_timer, TickTimer},
+};
+
+// CONSTANTS
+
+/// Ticks in a day, 20 minutes at 20 ticks per second
+const DEFAULT_DAY_LENGTH: u64 = 24000;
+/// Worlds start in the morning
+const START_TIME_OF_DAY: f32 = 0.1;
+
+const SKY_DAY_COLOR: Color = Color::rgb(126. / 255., 200. / 255., 1.);
+const SKY_NIGHT_COLOR: Color = Color::rgb(0.02, 0.03, 0.09);
+const SKY_SUNRISE_COLOR: Color = Color::rgb(0.98, 0.62, 0.45);
+const SKY_SUNSET_COLOR: Color = Color::rgb(0.93, 0.45, 0.3);
+
+/// Sky color through the day, from sunrise to the next one
+const SKY_COLORS: [(f32, Color); 7] = [
+    (0., SKY_SUNRISE_COLOR),
+    (0.06, SKY_DAY_COLOR),
+    (0.44, SKY_DAY_COLOR),
+    (0.5, SKY_SUNSET_COLOR),
+    (0.56, SKY_NIGHT_COLOR),
+    (0.94, SKY_NIGHT_COLOR),
+    (1., SKY_SUNRISE_COLOR),
+];
+
+/// Fraction of the sunlight reaching the world through the day, from sunrise to the next one
+const DAYLIGHT: [(f32, f32); 7] = [
+    (0., 0.6),
+    (0.06, 1.),
+    (0.44, 1.),
+    (0.5, 0.6),
+    (0.56, 0.2),
+    (0.94, 0.2),
+    (1., 0.6),
+];
+/// Daylight only changes by steps as every chunk has to be drawn again when it does
+const DAYLIGHT_STEPS: f32 = 32.;
+
+/// Drawn behind the walls
+const SKY_Z_INDEX: f32 = -10.;
+/// Sun and moon go around the middle of the screen, low enough to rise from under its bottom
+const SKY_ORBIT_RADIUS: Vec2 = Vec2::new(600., 420.);
+const SKY_ORBIT_OFFSET: f32 = -120.;
+
+const SUN_SIZE: f32 = 90.;
+const SUN_COLOR: Color = Color::rgb(1., 0.95, 0.6);
+const MOON_SIZE: f32 = 70.;
+const MOON_COLOR: Color = Color::rgb(0.86, 0.88, 0.95);
+
+// PLUGINS
+
+pub struct WorldTimePlugin;
+
+impl Plugin for WorldTimePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Resources
+            .init_resource::<WorldTime>()
+            // Systems
+            .add_systems(
+                Update,
+                (
+                    advance_world_time.after(tick_timer),
+                    update_sky_color,
+                    update_daylight,
+                    spawn_sky_bodies,
+                    move_sky_bodies,
+                ),
+            )
+            // Reflection
+            .register_type::<WorldTime>();
+    }
+}
+
+// SYSTEMS
+
+fn advance_world_time(mut world_time: ResMut<WorldTime>, timer: Res<TickTimer>) {
+    world_time.ticks += timer.0.times_finished_this_tick() as u64;
+}
+
+fn update_sky_color(world_time: Res<WorldTime>, mut clear_color: ResMut<ClearColor>) {
+    clear_color.0 = world_time.sky_color();
+}
+
+fn update_daylight(world_time: Res<WorldTime>, mut daylight: ResMut<Daylight>) {
+    let stepped = (world_time.daylight() * DAYLIGHT_STEPS).round() / DAYLIGHT_STEPS;
+
+    daylight.set_if_neq(Daylight(stepped));
+}
+
+/// Sun and moon follow the camera as children of it
+fn spawn_sky_bodies(mut commands: Commands, cameras: Query<Entity, Added<MainCamera>>) {
+    for camera_ent in cameras.iter() {
+        commands.entity(camera_ent).with_children(|cb| {
+            cb.spawn((
+                Name::new("Sun"),
+                SkyBody { phase: 0. },
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: SUN_COLOR,
+                        custom_size: Some(Vec2::splat(SUN_SIZE)),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+
+            cb.spawn((
+                Name::new("Moon"),
+                SkyBody { phase: PI },
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: MOON_COLOR,
+                        custom_size: Some(Vec2::splat(MOON_SIZE)),
+                        ..default()
+                    },
+                    ..default()
+                },
+            ));
+        });
+    }
+}
+
+/// The sun rises on the right at sunrise, is highest at noon and sets on the left at sunset
+fn move_sky_bodies(
+    world_time: Res<WorldTime>,
+    camera: Query<&Transform, (With<MainCamera>, Without<SkyBody>)>,
+    mut sky_bodies: Query<(&SkyBody, &mut Transform)>,
+) {
+    let Ok(camera_transform) = camera.get_single() else { return };
+
+    for (sky_body, mut transform) in sky_bodies.iter_mut() {
+        let angle = world_time.time_of_day() * TAU + sky_body.phase;
+        let position = Vec2::new(angle.cos(), angle.sin()) * SKY_ORBIT_RADIUS;
+
+        transform.translation = Vec3::new(
+            position.x,
+            position.y + SKY_ORBIT_OFFSET,
+            // Local to the camera
+            SKY_Z_INDEX - camera_transform.translation.z,
+        );
+    }
+}
+
+// RESOURCES
+
+/// Time of the world counted in game ticks, days lasting [`day_length`] ticks
+#[derive(Resource, Reflect)]
+pub struct WorldTime {
+    pub ticks: u64,
+    pub day_length: u64,
+}
+
+impl WorldTime {
+    /// Part of the current day gone by, going from 0 at sunrise to 1 at the next one,
+    /// noon being at 0.25, sunset at 0.5 and midnight at 0.75
+    pub fn time_of_day(&self) -> f32 {
+        let day_length = self.day_length.max(1);
+
+        (self.ticks % day_length) as f32 / day_length as f32
+    }
+
+    pub fn sky_color(&self) -> Color {
+        sample(&SKY_COLORS, self.time_of_day(), |from, to, t| {
+            Color::rgb(
+                from.r() + (to.r() - from.r()) * t,
+                from.g() + (to.g() - from.g()) * t,
+                from.b() + (to.b() - from.b()) * t,
+            )
+        })
+    }
+
+    /// Fraction of the sunlight reaching the world
+    pub fn daylight(&self) -> f32 {
+        sample(&DAYLIGHT, self.time_of_day(), |from, to, t| {
+            from + (to - from) * t
+        })
+    }
+}
+
+impl Default for WorldTime {
+    fn default() -> Self {
+        Self {
+            ticks: (DEFAULT_DAY_LENGTH as f32 * START_TIME_OF_DAY) as u64,
+            day_length: DEFAULT_DAY_LENGTH,
+        }
+    }
+}
+
+// COMPONENTS
+
+/// Sun or moon, [`phase`] being its angle ahead of the sun
+#[derive(Component)]
+struct SkyBody {
+    phase: f32,
+}
+
+// FUNCTIONS
+
+/// Interpolates the value at [`t`] between the keys around it, keys being sorted by time
+fn sample<T: Copy>(keys: &[(f32, T)], t: f32, lerp: impl Fn(T, T, f32) -> T) -> T {
+    for pair in keys.windows(2) {
+        let ((from_t, from), (to_t, to)) = (pair[0], pair[1]);
+
+        if t <= to_t {
+            return lerp(from, to, (t - from_t) / (to_t - from_t));
+        }
+    }
+
+    keys[keys.len() - 1].1
+}
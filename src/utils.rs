@@ -42,3 +42,15 @@ pub fn in_bounds_y(below: Option<i32>, above: Option<i32>, y: i32) -> bool {
     let b = cmp_opt_lit(above, y, Less);
     (a == Greater || a == Equal) && (b == Less || b == Equal)
 }
+
+/// Returns a value in [0, 1) that only depends on the arguments, for when noise is too smooth
+pub fn hash_noise(seed: u64, x: i32, y: i32) -> f32 {
+    // splitmix64 finalizer
+    let mut h = seed ^ ((x as u32 as u64) << 32 | y as u32 as u64);
+    h = h.wrapping_add(0x9e3779b97f4a7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h ^= h >> 31;
+
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
@@ -1,21 +1,60 @@
 use crate::{
     block::{BlockBundle, BlockGraphics, BLOCK_SIZE},
+    chunk::{
+        chunk_origin, grid_to_world, BlockGrid, ChunkData, ChunkView, LoadedChunks, WorldGrid,
+        CHUNK_HEIGHT,
+    },
+    item::spawn_item,
     item_kind::ItemKind,
     player::Player,
-    utils::in_bounds_y as inside,
+    save::{ChunkDiff, WorldSave},
+    structure::{Structure, StructurePlacement, Structures},
+    tick::RandomTick,
+    utils::{hash_noise, in_bounds_y as inside},
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    math::vec2,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashSet},
 };
-use bevy::{math::vec2, prelude::*};
 
 use bevy_inspector_egui::{prelude::*};
+use bevy_rapier2d::prelude::*;
 use bracket_noise::prelude::*;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+use std::{f32::consts::TAU, iter};
 
 // CONSTANTS
-const WORLD_OFFSET: Vec3 = Vec3::new(0., -BLOCK_SIZE * 40., 0.);
-const CHUNK_SIZE: i32 = 16;
+pub const WORLD_OFFSET: Vec3 = Vec3::new(0., -BLOCK_SIZE * 40., 0.);
+pub const CHUNK_SIZE: i32 = 16;
 const CHUNK_RENDER_DISTANCE: i32 = 8;
 
+const WORLD_SETTINGS_PATH: &str = "world.settings.ron";
+
+const BLOB_NOISE_CHANNEL: u64 = 1;
+const DENSITY_NOISE_CHANNEL: u64 = 2;
+const BLOB_MAP_STEP: i32 = 1000;
+
+/// Row of [`hash_noise`] deciding which terrain depressions hold a lake
+const LAKE_HASH_ROW: i32 = 1;
+
+/// Moves the seed of the worms and ravines away from the one of the trees
+const CARVER_SEED_OFFSET: u64 = 1;
+/// Most a worm can turn by each block, in radians
+const WORM_TURN: f32 = 0.35;
+
+/// Moves the seed of the structures away from the one of the trees
+const STRUCTURE_SEED_OFFSET: u64 = 2;
+/// Moves the seed of the plants away from the one of the trees
+const PLANT_SEED_OFFSET: u64 = 3;
+
+/// Row of the noise map the terrain height is read from
+const TERRAIN_NOISE_Y: f32 = 13.;
+
 // PLUGINS
 
 pub struct WorldPlugin;
@@ -24,153 +63,25 @@ impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
         app
             // Resources
-            .insert_resource(WorldSettings {
-                seed: 0,
-                octaves: 2,
-                lacunarity: 5.0,
-
-                biomes: Biomes {
-                    frequency: 0.8,
-                    divider: 100.,
-
-                    plain: BiomeSettings {
-                        v: 0.,
-
-                        terrain_frequency: 1.,
-                        terrain_divider: 200.,
-
-                        // cave_frequency: 1.1,
-                        // cave_divider: 10.,
-                        cave_frequency: 13.,
-                        cave_divider: 200.,
-
-                        height_multiplier: 40.,
-                        height_addition: 90.,
-
-                        // air_porbality: 0.15,
-                        // air_porbality: 0.008,
-                        // air_porbality: 0.25,
-                        air_porbality: -0.13,
-                        exposed_block_top_layer_height: 1,
-                        exposed_block_layer_height: 3,
-                        tree_kind: TreeKind::Oak,
-                        tree_chance: 6,
-
-                        exposed_block_top: ItemKind::Grass,
-                        exposed_block: ItemKind::Dirt,
-
-                        ores_map_step: 10,
-
-                        coal: OreSettings {
-                            rarity: 0.5,
-                            size: -0.18,
-                            divider: 3.,
-                            below: None,
-                            above: Some(20),
-                        },
-
-                        copper: OreSettings {
-                            rarity: 1.,
-                            size: -0.18,
-                            divider: 4.,
-                            below: None,
-                            above: Some(20),
-                        },
-
-                        iron: OreSettings {
-                            rarity: 3.5,
-                            size: -0.18,
-                            divider: 10.,
-                            below: None,
-                            above: None,
-                        },
-
-                        gold: OreSettings {
-                            rarity: 4.5,
-                            size: -0.25,
-                            divider: 8.,
-                            below: Some(30),
-                            above: Some(5),
-                        },
-
-                        diamond: OreSettings {
-                            rarity: 3.5,
-                            size: -0.25,
-                            divider: 7.,
-                            below: Some(15),
-                            above: None,
-                        },
-                    },
-                    // desert: BiomeSettings {
-                    //     v: 0.,
-
-                    //     terrain_frequency: 2.,
-                    //     terrain_divider: 140.,
-
-                    //     cave_frequency: 3.5,
-                    //     cave_divider: 140.,
-
-                    //     height_multiplier: 40.,
-                    //     height_addition: 90.,
-
-                    //     air_porbality: 0.18,
-                    //     exposed_block_top_layer_height: 4,
-                    //     exposed_block_layer_height: 2,
-                    //     tree_kind: TreeKind::Cactus,
-                    //     tree_chance: 20,
-
-                    //     exposed_block_top: BlockKind::Sand,
-                    //     exposed_block: BlockKind::Sandstone,
-
-                    //     ores_map_step: 10,
-
-                    //     coal: OreSettings {
-                    //         rarity: 0.5,
-                    //         size: -0.18,
-                    //         divider: 3.,
-                    //         below: None,
-                    //         above: Some(20),
-                    //     },
-
-                    //     copper: OreSettings {
-                    //         rarity: 1.,
-                    //         size: -0.18,
-                    //         divider: 4.,
-                    //         below: None,
-                    //         above: Some(20),
-                    //     },
-
-                    //     iron: OreSettings {
-                    //         rarity: 3.5,
-                    //         size: -0.18,
-                    //         divider: 10.,
-                    //         below: None,
-                    //         above: None,
-                    //     },
-
-                    //     gold: OreSettings {
-                    //         rarity: 4.5,
-                    //         size: -0.25,
-                    //         divider: 8.,
-                    //         below: Some(50),
-                    //         above: None,
-                    //     },
-
-                    //     diamond: OreSettings {
-                    //         rarity: 3.5,
-                    //         size: -0.25,
-                    //         divider: 7.,
-                    //         below: Some(15),
-                    //         above: None,
-                    //     },
-                    // },
-                },
-            })
             .insert_resource(PlayerChunkPosition(0))
+            .insert_resource(WorldSettingsHandle::default())
+            // Assets
+            .add_asset::<WorldSettings>()
+            .init_asset_loader::<WorldSettingsLoader>()
             // Systems
             // .add_systems(Startup, spawn_test_platform)
-            .add_systems(Startup, spawn_world)
-            .add_systems(Update, (update_player_chunk_pos, refresh_world))
+            .add_systems(Startup, (spawn_world, load_world_settings))
+            .add_systems(
+                Update,
+                (
+                    update_player_chunk_pos,
+                    apply_world_settings,
+                    check_structure_biomes,
+                    refresh_world,
+                    grow_saplings,
+                    decay_leaves,
+                ),
+            )
             // Reflection
             // .register_type::<WorldSettings>()
             // .add_plugins(ResourceInspectorPlugin::<WorldSettings>::default())
@@ -186,7 +97,7 @@ pub struct World;
 #[derive(Component)]
 pub struct Chunk;
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkPosition(pub i32);
 
 #[derive(Component)]
@@ -198,6 +109,52 @@ fn spawn_world(mut commands: Commands) {
     commands.spawn((WorldBundle::default(), Name::new("World")));
 }
 
+fn load_world_settings(
+    mut settings_handle: ResMut<WorldSettingsHandle>,
+    asset_server: Res<AssetServer>,
+) {
+    settings_handle.0 = asset_server.load(WORLD_SETTINGS_PATH);
+}
+
+/// Copies the settings asset into the [`WorldSettings`] resource every time it is (re)loaded,
+/// which makes [`refresh_world`] generate the world again
+fn apply_world_settings(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<WorldSettings>>,
+    settings_assets: Res<Assets<WorldSettings>>,
+    settings_handle: Res<WorldSettingsHandle>,
+) {
+    for event in asset_events.iter() {
+        let (AssetEvent::Created { handle } | AssetEvent::Modified { handle }) = event else { continue };
+
+        if *handle != settings_handle.0 {
+            continue;
+        }
+
+        if let Some(settings) = settings_assets.get(handle) {
+            info!("Applying world settings from {}", WORLD_SETTINGS_PATH);
+            commands.insert_resource(settings.clone());
+        }
+    }
+}
+
+/// Settings and structures are separate assets,
+/// so the biomes of the structures can only be checked once both are loaded
+fn check_structure_biomes(
+    settings: Option<Res<WorldSettings>>,
+    structures: Option<Res<Structures>>,
+) {
+    let (Some(settings), Some(structures)) = (settings, structures) else { return };
+
+    if !settings.is_changed() && !structures.is_changed() {
+        return;
+    }
+
+    if let Err(err) = settings.validate_structures(&structures.0) {
+        error!("Invalid structures: {}", err);
+    }
+}
+
 fn update_player_chunk_pos(
     player_transform: Query<&GlobalTransform, With<Player>>,
     mut player_chunk_pos: ResMut<PlayerChunkPosition>,
@@ -214,9 +171,11 @@ fn refresh_world(
     mut commands: Commands,
     world: Query<Entity, With<World>>,
     chunks_pos: Query<(Entity, &ChunkPosition), With<Chunk>>,
+    mut loaded_chunks: ResMut<LoadedChunks>,
     player_chunk_pos: Res<PlayerChunkPosition>,
-    settings: Res<WorldSettings>,
-    block_graphics: Res<BlockGraphics>,
+    settings: Option<Res<WorldSettings>>,
+    structures: Option<Res<Structures>>,
+    world_save: Res<WorldSave>,
     // mut first_time_not: Local<bool>,
 ) {
     // let first_time = !*first_time_not;
@@ -249,24 +208,32 @@ fn refresh_world(
     //         }
     //     });
 
-    if !player_chunk_pos.is_changed() {
-        return;
-    }
+    // Nothing can be generated until the settings and structures are loaded
+    let (Some(settings), Some(structures)) = (settings, structures) else { return };
 
     let world_ent = world.single();
 
-    let mut rng = ChaCha8Rng::seed_from_u64(settings.seed);
+    if settings.is_changed() || structures.is_changed() {
+        // Every chunk has to be generated again with the new settings
+        for chunk_pos in chunks_pos.iter() {
+            commands.entity(chunk_pos.0).despawn_recursive();
+        }
 
-    let mut noise = FastNoise::seeded(settings.seed);
-    noise.set_noise_type(NoiseType::PerlinFractal);
-    noise.set_fractal_octaves(settings.octaves);
-    noise.set_fractal_lacunarity(settings.lacunarity);
+        loaded_chunks.0.clear();
+    } else if !player_chunk_pos.is_changed() {
+        return;
+    }
 
     for chunk_pos in chunks_pos.iter() {
+        if !loaded_chunks.0.contains_key(chunk_pos.1) {
+            continue;
+        }
+
         if player_chunk_pos.0 < chunk_pos.1 .0 - CHUNK_RENDER_DISTANCE
             || player_chunk_pos.0 > chunk_pos.1 .0 + CHUNK_RENDER_DISTANCE
         {
             commands.entity(chunk_pos.0).despawn_recursive();
+            loaded_chunks.0.remove(chunk_pos.1);
         }
     }
 
@@ -274,249 +241,854 @@ fn refresh_world(
         for i in (player_chunk_pos.0 - CHUNK_RENDER_DISTANCE)
             ..=(player_chunk_pos.0 + CHUNK_RENDER_DISTANCE)
         {
-            if !chunks_pos.iter().any(|x| i == x.1 .0) {
-                generate_chunk(i, cb, &mut noise, &mut rng, &settings, &block_graphics);
+            if loaded_chunks.0.contains_key(&ChunkPosition(i)) {
+                continue;
             }
+
+            let data = generate_chunk(
+                i,
+                &settings,
+                &structures.0,
+                world_save.chunk(&ChunkPosition(i)),
+            );
+
+            let chunk_ent = cb
+                .spawn((ChunkBundle::new(i, data), Name::new(format!("Chunk {}", i))))
+                .id();
+
+            loaded_chunks.0.insert(ChunkPosition(i), chunk_ent);
         }
     });
 }
 
+/// Saplings grow into their tree when randomly ticked, as long as its tallest trunk fits above them
+fn grow_saplings(
+    mut random_ticks: EventReader<RandomTick>,
+    mut grid: WorldGrid,
+    settings: Option<Res<WorldSettings>>,
+) {
+    let Some(settings) = settings else { return };
+
+    for tick in random_ticks.iter() {
+        let Some(tree) = settings.trees.iter().find(|tree| tree.sapling == Some(tick.kind)) else { continue };
+
+        if grid.get_block(tick.pos) != Some(tick.kind) {
+            continue;
+        }
+
+        if (1..=tree.max_height).any(|i| grid.get_block(tick.pos + IVec2::new(0, i)).is_some()) {
+            continue;
+        }
+
+        grid.set_block(tick.pos, None);
+        spawn_tree(tree, rand::random(), tick.pos.x, tick.pos.y, &mut grid);
+    }
+}
+
+/// Leaves cut off from the logs of their tree rot away when randomly ticked,
+/// sometimes dropping the sapling of the tree
+fn decay_leaves(
+    mut commands: Commands,
+    mut random_ticks: EventReader<RandomTick>,
+    mut grid: WorldGrid,
+    settings: Option<Res<WorldSettings>>,
+    block_graphics: Res<BlockGraphics>,
+) {
+    let Some(settings) = settings else { return };
+    let mut rng = rand::thread_rng();
+
+    for tick in random_ticks.iter() {
+        let Some(tree) = settings.trees.iter().find(|tree| tree.leaves == Some(tick.kind)) else { continue };
+
+        if grid.get_block(tick.pos) != Some(tick.kind)
+            || is_leaf_attached(tick.pos, &grid, &settings)
+        {
+            continue;
+        }
+
+        grid.set_block(tick.pos, None);
+
+        let Some(sapling) = tree.sapling else { continue };
+
+        if rng.gen_bool(settings.leaf_decay.sapling_chance as f64) {
+            spawn_item(
+                &mut commands,
+                sapling,
+                grid_to_world(tick.pos),
+                ExternalImpulse::default(),
+                &block_graphics,
+            );
+        }
+    }
+}
+
+/// Only depends on the settings, the position of the chunk and the changes made to it,
+/// so a chunk is the same no matter when or from where it is generated
 fn generate_chunk(
     chunk_x: i32,
-    cb: &mut ChildBuilder,
-    noise: &mut FastNoise,
-    rng: &mut ChaCha8Rng,
-    stgs: &Res<WorldSettings>,
-    block_graphics: &Res<BlockGraphics>,
-) {
-    cb.spawn((
-        ChunkBundle::new(chunk_x),
-        Name::new(format!("Chunk {}", chunk_x)),
-    ))
-    .with_children(|cb| {
-        let mut blocks = Vec::<Vec2>::new();
-
-        noise.set_frequency(stgs.biomes.frequency);
-        for x in (CHUNK_SIZE * chunk_x) + 1..=(CHUNK_SIZE * (chunk_x) + CHUNK_SIZE) {
-            noise.set_frequency(stgs.biomes.frequency);
-            let _biome_v = noise.get_noise(x as f32 / stgs.biomes.divider, 1.);
-            // let bstgs = match biome_v {
-            //     _ if biome_v <= stgs.biomes.desert.v => &stgs.biomes.desert,
-            //     _ if biome_v <= stgs.biomes.plain.v => &stgs.biomes.plain,
-            //     _ => return,
-            // };
-
-            let bstgs = &stgs.biomes.plain;
-
-            noise.set_frequency(bstgs.terrain_frequency);
-
-            let height = noise
-                .get_noise(x as f32 / bstgs.terrain_divider, 1. * bstgs.cave_frequency)
-                * bstgs.height_multiplier
-                + bstgs.height_addition;
-
-            for y in 0..=height as i32 {
-                let name = Name::new(format!("Block {}:{}", x, y));
-
-                if y == 0 {
-                    cb.spawn((
-                        BlockBundle::new(
-                            ItemKind::Bedrock,
-                            vec2(x as f32 * BLOCK_SIZE, 0.),
-                            &block_graphics,
-                        ),
-                        name,
-                    ));
+    stgs: &WorldSettings,
+    structures: &[Structure],
+    diff: Option<&ChunkDiff>,
+) -> ChunkData {
+    let mut noise = world_noise(stgs);
+    let mut blob_noise = channel_noise(stgs, BLOB_NOISE_CHANNEL);
+    let mut density_noise = channel_noise(stgs, DENSITY_NOISE_CHANNEL);
+    let mut data = ChunkData::default();
+    let origin = chunk_origin(chunk_x);
+    let lakes = lake_levels(chunk_x, &mut noise, stgs);
+    let carved = carved_cells(chunk_x, &mut noise, stgs);
+
+    for lx in 0..CHUNK_SIZE {
+        let x = origin + lx;
+
+        let Column {
+            biome: bstgs,
+            surface,
+            height,
+            density,
+        } = column(x, &mut noise, stgs);
+
+        // Land meeting the sea is covered with sand
+        let (exposed_block_top, exposed_block) = if is_beach(height, stgs) {
+            (stgs.beach.block, stgs.beach.block)
+        } else {
+            (surface.exposed_block_top, surface.exposed_block)
+        };
+
+        // Ground cells between the current one and the air above it
+        let mut depth = 0;
+
+        for y in (0..=height + density.ceil() as i32).rev() {
+            if y == 0 {
+                data.set_block(lx, y, Some(ItemKind::Bedrock));
+
+                continue;
+            }
 
-                    continue;
+            if !is_ground(x, y, height, density, &mut density_noise, stgs) {
+                depth = 0;
+                continue;
+            }
+
+            let deepslate = is_deepslate(x, y, &mut noise, &stgs.deepslate);
+            // Peaks are left bare
+            let exposed = depth
+                < surface.exposed_block_layer_height + surface.exposed_block_top_layer_height
+                && !surface.peak_height.is_some_and(|peak| y > peak);
+            let exposed_top = exposed && depth < surface.exposed_block_top_layer_height;
+            depth += 1;
+
+            // Walls fill the whole ground, caves included
+            let wall = if exposed {
+                exposed_block
+            } else if deepslate {
+                ItemKind::Deepslate
+            } else {
+                ItemKind::Stone
+            };
+            data.set_wall(lx, y, Some(wall));
+
+            if carved.contains(&IVec2::new(lx, y)) || is_cavern(x, y, &mut noise, bstgs) {
+                continue;
+            }
+
+            let mut kind = ItemKind::Stone;
+
+            // Pockets of other materials replacing their host block
+            for blob in stgs.blobs.iter() {
+                if blob.host == kind && is_blob(x, y, &mut blob_noise, blob) {
+                    kind = blob.kind;
                 }
+            }
 
-                noise.set_frequency(bstgs.cave_frequency);
-
-                let v =
-                    noise.get_noise(x as f32 / bstgs.cave_divider, y as f32 / bstgs.cave_divider);
-
-                if v > bstgs.air_porbality
-                    && !blocks.iter().any(|&b| b.x == x as f32 && b.y == y as f32)
-                {
-                    let mut kind = ItemKind::Stone;
-
-                    noise.set_frequency(bstgs.coal.rarity);
-                    let coal_v = noise
-                        .get_noise(x as f32 / bstgs.coal.divider, y as f32 / bstgs.coal.divider);
-
-                    noise.set_frequency(bstgs.copper.rarity);
-                    let copper_v = noise.get_noise(
-                        (x + bstgs.ores_map_step) as f32 / bstgs.copper.divider,
-                        (y + bstgs.ores_map_step) as f32 / bstgs.copper.divider,
-                    );
-
-                    noise.set_frequency(bstgs.iron.rarity);
-                    let iron_v = noise.get_noise(
-                        (x + bstgs.ores_map_step * 2) as f32 / bstgs.iron.divider,
-                        (y + bstgs.ores_map_step * 2) as f32 / bstgs.iron.divider,
-                    );
-
-                    noise.set_frequency(bstgs.gold.rarity);
-                    let gold_v = noise.get_noise(
-                        (x + bstgs.ores_map_step * 3) as f32 / bstgs.gold.divider,
-                        (y + bstgs.ores_map_step * 3) as f32 / bstgs.gold.divider,
-                    );
-
-                    noise.set_frequency(bstgs.diamond.rarity);
-                    let diamond_v = noise.get_noise(
-                        (x + bstgs.ores_map_step * 4) as f32 / bstgs.diamond.divider,
-                        (y + bstgs.ores_map_step * 4) as f32 / bstgs.diamond.divider,
-                    );
-
-                    match true {
-                        _ if is_ore(diamond_v, y, &bstgs.diamond) => kind = ItemKind::DiamondOre,
-                        _ if is_ore(gold_v, y, &bstgs.gold) => kind = ItemKind::GoldOre,
-                        _ if is_ore(iron_v, y, &bstgs.iron) => kind = ItemKind::IronOre,
-                        _ if is_ore(copper_v, y, &bstgs.copper) => kind = ItemKind::CopperOre,
-                        _ if is_ore(coal_v, y, &bstgs.coal) => kind = ItemKind::CoalOre,
-                        _ => {}
-                    }
+            // The first ore of the list growing in this cell wins
+            if let Some(ore) = bstgs
+                .ores
+                .iter()
+                .find(|ore| ore.host == kind && is_ore(x, y, &mut noise, bstgs.ores_map_step, ore))
+            {
+                kind = ore.kind;
+            }
 
-                    if height as i32 - y
-                        < bstgs.exposed_block_layer_height + bstgs.exposed_block_top_layer_height
-                    {
-                        kind = bstgs.exposed_block;
-                    }
+            if deepslate {
+                kind = kind.deepslate_variant();
+            }
 
-                    if height as i32 - y < bstgs.exposed_block_top_layer_height {
-                        kind = bstgs.exposed_block_top;
-                    }
+            if exposed {
+                kind = exposed_block;
+            }
 
-                    if y == height as i32 {
-                        if rng.gen_bool(1. / bstgs.tree_chance as f64) {
-                            spawn_tree(
-                                bstgs.tree_kind,
-                                x as f32,
-                                y as f32 + 1.,
-                                &mut blocks,
-                                cb,
-                                &block_graphics,
-                            );
-                        }
-                    }
+            if exposed_top {
+                kind = exposed_block_top;
+            }
 
-                    cb.spawn((
-                        BlockBundle::new(
-                            kind,
-                            vec2(x as f32 * BLOCK_SIZE, y as f32 * BLOCK_SIZE),
-                            &block_graphics,
-                        ),
-                        name,
-                    ));
+            data.set_block(lx, y, Some(kind));
+        }
+
+        if let Some(level) = lakes[lx as usize] {
+            // The bed is laid over caves too so the water can't leak in them
+            for y in (height - stgs.lakes.bed_depth + 1).max(1)..=height {
+                data.set_block(lx, y, Some(surface.lake_bed));
+            }
 
-                    blocks.push(vec2(x as f32, y as f32));
+            for y in height + 1..=level {
+                if data.get_block(lx, y).is_none() {
+                    data.set_block(lx, y, Some(ItemKind::Water));
                 }
             }
         }
-    });
+
+        // Caves deep enough are flooded with lava, leaving pools at their bottom
+        for y in 1..=stgs.lava_level {
+            if data.get_block(lx, y).is_none() {
+                data.set_block(lx, y, Some(ItemKind::Lava));
+            }
+        }
+
+        // Everything open under the sea level is flooded, caves of the sea floor included
+        if height < stgs.sea_level {
+            for y in 1..=stgs.sea_level {
+                if data.get_block(lx, y).is_none() {
+                    data.set_block(lx, y, Some(ItemKind::Water));
+                }
+            }
+        }
+    }
+
+    // Trees growing near the edges of the neighbouring chunks hang over this one
+    for cx in chunk_x - 1..=chunk_x + 1 {
+        for (kind, root, seed) in chunk_trees(cx, &mut noise, stgs) {
+            let Some(tree) = stgs.trees.iter().find(|tree| tree.kind == kind) else { continue };
+            spawn_tree(tree, seed, root.x - origin, root.y, &mut data);
+        }
+    }
+
+    // Plants come after the trees so they don't end up stuck in their trunks
+    spawn_plants(chunk_x, &mut noise, stgs, &mut data);
+
+    // Structures go over the trees, and can start in the chunks on the left too
+    for cx in chunk_x - structure_reach(structures)..=chunk_x {
+        for (structure, corner) in chunk_structures(cx, &mut noise, stgs, structures) {
+            structure.place(corner - IVec2::new(origin, 0), &mut data);
+        }
+    }
+
+    // Replay the changes made to the chunk since it was first generated
+    if let Some(diff) = diff {
+        for (&(layer, pos), &kind) in diff.blocks.iter() {
+            data.set(layer, pos.x - origin, pos.y, kind);
+        }
+
+        for (&pos, &level) in diff.levels.iter() {
+            data.set_level(pos.x - origin, pos.y, level);
+        }
+    }
+
+    data
 }
 
-fn is_ore(v: f32, y: i32, ore_stgs: &OreSettings) -> bool {
-    v < ore_stgs.size && inside(ore_stgs.below, ore_stgs.above, y)
+/// Roots of the trees of a chunk with the seed of their shape,
+/// rolled column by column with the chunk's own generator
+fn chunk_trees(
+    chunk_x: i32,
+    noise: &mut FastNoise,
+    stgs: &WorldSettings,
+) -> Vec<(TreeKind, IVec2, u64)> {
+    let mut rng = chunk_rng(stgs.seed, chunk_x);
+    let mut density_noise = channel_noise(stgs, DENSITY_NOISE_CHANNEL);
+    let mut trees = Vec::new();
+    let lakes = lake_levels(chunk_x, noise, stgs);
+    let carved = carved_cells(chunk_x, noise, stgs);
+
+    for (x, lake) in (chunk_origin(chunk_x)..chunk_origin(chunk_x + 1)).zip(lakes) {
+        let col = column(x, noise, stgs);
+        let top = ground_top(x, &col, &mut density_noise, stgs);
+
+        let cave = carved.contains(&IVec2::new(x - chunk_origin(chunk_x), top))
+            || is_cavern(x, top, noise, col.biome);
+
+        if cave || lake.is_some() || top < stgs.sea_level {
+            continue;
+        }
+
+        if let Some(kind) = pick_tree(&col.surface.trees, &mut rng) {
+            trees.push((kind, IVec2::new(x, top + 1), rng.gen()));
+        }
+    }
+
+    trees
 }
 
-fn spawn_tree(
-    kind: TreeKind,
-    x: f32,
-    y: f32,
-    blocks: &mut Vec<Vec2>,
-    commands: &mut ChildBuilder,
-    block_graphics: &Res<BlockGraphics>,
-) {
-    use TreeKind::*;
-    match kind {
-        Oak => {
-            for i in 0..=5 {
-                if blocks
-                    .iter()
-                    .any(|&b| b.x == x as f32 && b.y == y + i as f32)
-                {
+/// Rolls one of the weighted trees, [`None`] standing for no tree
+fn pick_tree(trees: &[WeightedTree], rng: &mut ChaCha8Rng) -> Option<TreeKind> {
+    let total = trees.iter().map(|tree| tree.weight).sum::<u32>();
+    if total == 0 {
+        return None;
+    }
+
+    let mut roll = rng.gen_range(0..total);
+    for tree in trees {
+        if roll < tree.weight {
+            return tree.kind;
+        }
+
+        roll -= tree.weight;
+    }
+
+    None
+}
+
+/// Scatters the plants of the biomes on the topmost block of each column of a chunk,
+/// rolled with the chunk's own generator
+fn spawn_plants(chunk_x: i32, noise: &mut FastNoise, stgs: &WorldSettings, data: &mut ChunkData) {
+    let mut rng = chunk_rng(stgs.seed.wrapping_add(PLANT_SEED_OFFSET), chunk_x);
+
+    for lx in 0..CHUNK_SIZE {
+        let col = column(chunk_origin(chunk_x) + lx, noise, stgs);
+
+        let top = (1..CHUNK_HEIGHT)
+            .rev()
+            .find_map(|y| Some((y, data.get_block(lx, y)?)));
+        let Some((y, ground)) = top else { continue };
+
+        for plant in col.surface.plants.iter() {
+            if plant.grounds.contains(&ground) && rng.gen_bool(plant.density as f64) {
+                data.set_block(lx, y + 1, Some(plant.kind));
+                break;
+            }
+        }
+    }
+}
+
+/// Structures starting in the chunk with the grid position of their bottom left corner,
+/// rolled with the chunk's own generator so they are the same from every chunk they reach
+fn chunk_structures<'a>(
+    chunk_x: i32,
+    noise: &mut FastNoise,
+    stgs: &WorldSettings,
+    structures: &'a [Structure],
+) -> Vec<(&'a Structure, IVec2)> {
+    let mut rng = chunk_rng(stgs.seed.wrapping_add(STRUCTURE_SEED_OFFSET), chunk_x);
+    let mut density_noise = channel_noise(stgs, DENSITY_NOISE_CHANNEL);
+    let mut placed = Vec::new();
+    let origin = chunk_origin(chunk_x);
+
+    for structure in structures {
+        // Every roll is made even for structures that aren't placed,
+        // so one structure never changes where the others go
+        let placed_here = rng.gen_ratio(1, structure.rarity);
+        let x = rng.gen_range(origin..origin + CHUNK_SIZE);
+        let depth = rng.gen::<f32>();
+
+        if !placed_here {
+            continue;
+        }
+
+        let col = column(x, noise, stgs);
+        if !structure.biomes.is_empty() && !structure.biomes.contains(&col.biome.name) {
+            continue;
+        }
+
+        let y = match structure.placement {
+            StructurePlacement::Surface { sink } => {
+                let top = ground_top(x, &col, &mut density_noise, stgs);
+                if top < stgs.sea_level {
                     continue;
                 }
 
-                let kind = if i >= 3 {
-                    ItemKind::LeafedOakLog
-                } else {
-                    ItemKind::OakLog
-                };
-
-                commands.spawn((
-                    BlockBundle::non_collidable(
-                        kind,
-                        vec2(x * BLOCK_SIZE, (y + i as f32) * BLOCK_SIZE),
-                        block_graphics,
-                    ),
-                    Name::new(format!("Block {}:{}", x, y + i as f32)),
-                ));
+                top + 1 - sink
+            }
+            StructurePlacement::Underground { below, above } => {
+                let bottom = above.unwrap_or(1);
+                let top = below
+                    .unwrap_or(col.height)
+                    .min(col.height - structure.height());
+                if bottom > top {
+                    continue;
+                }
+
+                bottom + ((top - bottom + 1) as f32 * depth) as i32
+            }
+        };
 
-                blocks.push(vec2(x, y + i as f32));
+        placed.push((structure, IVec2::new(x, y)));
+    }
+
+    placed
+}
+
+/// How many chunks away a structure can start and still reach a chunk
+fn structure_reach(structures: &[Structure]) -> i32 {
+    let widest = structures.iter().map(Structure::width).max().unwrap_or(0);
+
+    (widest + CHUNK_SIZE - 1) / CHUNK_SIZE
+}
+
+/// Cells of the chunk dug out by the worms and ravines of the chunks around it, in chunk space
+fn carved_cells(chunk_x: i32, noise: &mut FastNoise, stgs: &WorldSettings) -> HashSet<IVec2> {
+    let origin = chunk_origin(chunk_x);
+    let mut cells = HashSet::new();
+
+    let reach = carver_reach(stgs);
+
+    for cx in chunk_x - reach..=chunk_x + reach {
+        for (center, radius) in chunk_carvers(cx, noise, stgs) {
+            let min = (center - radius).floor().as_ivec2();
+            let max = (center + radius).ceil().as_ivec2();
+
+            for x in min.x.max(origin)..=max.x.min(origin + CHUNK_SIZE - 1) {
+                // Bedrock is never carved
+                for y in min.y.max(1)..=max.y {
+                    if IVec2::new(x, y).as_vec2().distance_squared(center) <= radius * radius {
+                        cells.insert(IVec2::new(x - origin, y));
+                    }
+                }
             }
+        }
+    }
+
+    cells
+}
+
+/// Circles dug by the worms and ravines starting in the chunk,
+/// rolled with the chunk's own generator so they are the same from every chunk they reach
+fn chunk_carvers(chunk_x: i32, noise: &mut FastNoise, stgs: &WorldSettings) -> Vec<(Vec2, f32)> {
+    let mut rng = chunk_rng(stgs.seed.wrapping_add(CARVER_SEED_OFFSET), chunk_x);
+    let mut circles = Vec::new();
+    let origin = chunk_origin(chunk_x);
+
+    // The column in the middle of the chunk decides how it gets carved
+    let Column { biome, height, .. } = column(origin + CHUNK_SIZE / 2, noise, stgs);
+
+    let wstgs = &biome.worms;
+    let worms = wstgs.frequency as u32 + rng.gen_bool(wstgs.frequency.fract() as f64) as u32;
+    let bottom = wstgs.above.unwrap_or(1);
+    let top = wstgs.below.unwrap_or(height).min(height);
+
+    for _ in 0..worms {
+        if bottom > top {
+            break;
+        }
+
+        let start_x = rng.gen_range(origin..origin + CHUNK_SIZE);
+        let start_y = rng.gen_range(bottom..=top);
+        let mut pos = IVec2::new(start_x, start_y).as_vec2();
+        let mut angle = rng.gen_range(0. ..TAU);
+
+        for _ in 0..wstgs.length {
+            circles.push((pos, wstgs.radius));
+
+            // Tunnels wind around but mostly stay level
+            pos += Vec2::new(angle.cos(), angle.sin() * 0.5);
+            angle += rng.gen_range(-WORM_TURN..=WORM_TURN);
+        }
+    }
+
+    let rstgs = &biome.ravines;
+    if rng.gen_bool(rstgs.chance as f64) {
+        let mut x = rng.gen_range(origin..origin + CHUNK_SIZE) as f32;
+        let top = rstgs.below.unwrap_or(height).min(height);
+        let bottom = rstgs.above.unwrap_or(1).max(top - rstgs.depth);
+
+        for y in (bottom..=top).rev() {
+            // Ravines are widest at the top and close up at the bottom
+            let t = (y - bottom) as f32 / (top - bottom).max(1) as f32;
+            let radius = (rstgs.width / 2. * t.sqrt()).max(0.5);
+            circles.push((Vec2::new(x, y as f32), radius));
+
+            x += rng.gen_range(-0.5..=0.5);
+        }
+    }
+
+    circles
+}
+
+/// How many chunks away a worm can start and still reach a chunk
+fn carver_reach(stgs: &WorldSettings) -> i32 {
+    let longest = stgs
+        .biomes
+        .list
+        .iter()
+        .chain(iter::once(&stgs.biomes.ocean))
+        .map(|bstgs| bstgs.worms.length)
+        .max()
+        .unwrap_or(0);
+
+    // Ravines barely wander so worms always reach further
+    (longest + CHUNK_SIZE - 1) / CHUNK_SIZE + 1
+}
+
+/// Water surface of the columns of the chunk lying in a lake.
+/// Lakes fill local minima of the height map up to the lowest of their rims,
+/// only looking at the height map so they line up across chunks
+fn lake_levels(
+    chunk_x: i32,
+    noise: &mut FastNoise,
+    stgs: &WorldSettings,
+) -> [Option<i32>; CHUNK_SIZE as usize] {
+    let lstgs = &stgs.lakes;
+    let origin = chunk_origin(chunk_x);
+
+    // Lakes reaching the chunk have their bottom at most a radius away, and their rims another one
+    let start = origin - lstgs.radius * 2;
+    let heights = (start..origin + CHUNK_SIZE + lstgs.radius * 2)
+        .map(|x| column(x, noise, stgs).height)
+        .collect::<Vec<_>>();
+    let height = |x: i32| heights[(x - start) as usize];
+
+    let mut levels = [None; CHUNK_SIZE as usize];
+
+    for bottom_x in origin - lstgs.radius..origin + CHUNK_SIZE + lstgs.radius {
+        let bottom = height(bottom_x);
+
+        if height(bottom_x - 1) <= bottom || height(bottom_x + 1) < bottom {
+            continue;
+        }
+
+        if hash_noise(stgs.seed, bottom_x, LAKE_HASH_ROW) >= lstgs.chance {
+            continue;
+        }
+
+        let left_rim = (bottom_x - lstgs.radius..bottom_x)
+            .map(height)
+            .max()
+            .unwrap_or(bottom);
+        let right_rim = (bottom_x + 1..=bottom_x + lstgs.radius)
+            .map(height)
+            .max()
+            .unwrap_or(bottom);
+        let level = left_rim.min(right_rim).min(bottom + lstgs.max_depth);
+
+        // Lakes under the sea level are already part of it
+        if level <= bottom || level <= stgs.sea_level {
+            continue;
+        }
+
+        // Both rims are inside of the radius so the water stops before leaving it
+        let mut left = bottom_x;
+        while height(left - 1) < level {
+            left -= 1;
+        }
+
+        let mut right = bottom_x;
+        while height(right + 1) < level {
+            right += 1;
+        }
+
+        for x in left.max(origin)..=right.min(origin + CHUNK_SIZE - 1) {
+            let lake = &mut levels[(x - origin) as usize];
+            *lake = Some(lake.map_or(level, |other: i32| other.max(level)));
+        }
+    }
+
+    levels
+}
+
+fn world_noise(stgs: &WorldSettings) -> FastNoise {
+    channel_noise(stgs, 0)
+}
+
+/// Noise seeded apart from the terrain one so it can't line up with it
+fn channel_noise(stgs: &WorldSettings, channel: u64) -> FastNoise {
+    let mut noise = FastNoise::seeded(stgs.seed.wrapping_add(channel));
+    noise.set_noise_type(NoiseType::PerlinFractal);
+    noise.set_fractal_octaves(stgs.octaves);
+    noise.set_fractal_lacunarity(stgs.lacunarity);
+    noise
+}
+
+/// Each chunk draws from its own stream of the world seed
+fn chunk_rng(seed: u64, chunk_x: i32) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(chunk_x as i64 as u64);
+    rng
+}
+
+/// Picks the ocean if the continent noise of the column is low enough, otherwise
+/// the first biome whose threshold is above the biome noise of the column,
+/// columns above every threshold belong to the last biome
+fn biome_at<'a>(x: i32, noise: &mut FastNoise, stgs: &'a WorldSettings) -> &'a BiomeSettings {
+    noise.set_frequency(stgs.biomes.continent_frequency);
+    let continent_v = noise.get_noise(x as f32 / stgs.biomes.continent_divider, 2.);
+
+    if continent_v <= stgs.biomes.ocean_v {
+        return &stgs.biomes.ocean;
+    }
+
+    noise.set_frequency(stgs.biomes.frequency);
+    let biome_v = noise.get_noise(x as f32 / stgs.biomes.divider, 1.);
+
+    let biomes = &stgs.biomes.list;
+    biomes
+        .iter()
+        .find(|b| biome_v <= b.v)
+        .unwrap_or_else(|| biomes.last().expect("at least one biome is required"))
+}
+
+/// Near biome edges the height is the average of what every biome around the column
+/// would give it, and the surface is picked at random among them so it gets dithered
+fn column<'a>(x: i32, noise: &mut FastNoise, stgs: &'a WorldSettings) -> Column<'a> {
+    let blend_width = stgs.biomes.blend_width;
+
+    let nearby = (x - blend_width..=x + blend_width)
+        .map(|bx| biome_at(bx, noise, stgs))
+        .collect::<Vec<_>>();
+
+    let height = nearby
+        .iter()
+        .map(|bstgs| terrain_height(x, noise, bstgs))
+        .sum::<f32>()
+        / nearby.len() as f32;
+
+    let density = nearby.iter().map(|bstgs| bstgs.density).sum::<f32>() / nearby.len() as f32;
+
+    let surface_i = (hash_noise(stgs.seed, x, 0) * nearby.len() as f32) as usize;
+
+    Column {
+        biome: nearby[blend_width as usize],
+        surface: nearby[surface_i.min(nearby.len() - 1)],
+        height: height as i32,
+        density,
+    }
+}
+
+fn is_beach(height: i32, stgs: &WorldSettings) -> bool {
+    (stgs.sea_level - stgs.beach.depth..=stgs.sea_level + stgs.beach.height).contains(&height)
+}
+
+fn terrain_height(x: i32, noise: &mut FastNoise, bstgs: &BiomeSettings) -> f32 {
+    noise.set_frequency(bstgs.terrain_frequency);
+
+    noise.get_noise(x as f32 / bstgs.terrain_divider, TERRAIN_NOISE_Y) * bstgs.height_multiplier
+        + bstgs.height_addition
+}
+
+/// Highest ground cell of the column, overhangs can put it above or below the height map
+fn ground_top(x: i32, col: &Column, noise: &mut FastNoise, stgs: &WorldSettings) -> i32 {
+    (0..=col.height + col.density.ceil() as i32)
+        .rev()
+        .find(|&y| is_ground(x, y, col.height, col.density, noise, stgs))
+        .unwrap_or(0)
+}
+
+/// With no density the ground is everything under the height map, otherwise the density
+/// noise moves it up and down by up to [`Column::density`] blocks, leaving overhangs and arches
+fn is_ground(
+    x: i32,
+    y: i32,
+    height: i32,
+    density: f32,
+    noise: &mut FastNoise,
+    stgs: &WorldSettings,
+) -> bool {
+    if density <= 0. {
+        return y <= height;
+    }
+
+    noise.set_frequency(stgs.density.frequency);
+    let v = noise.get_noise(
+        x as f32 / stgs.density.divider,
+        y as f32 / stgs.density.divider,
+    );
+
+    (height - y) as f32 + v * density >= 0.
+}
+
+fn is_cavern(x: i32, y: i32, noise: &mut FastNoise, bstgs: &BiomeSettings) -> bool {
+    let cstgs = &bstgs.caverns;
+    if !inside(cstgs.below, cstgs.above, y) {
+        return false;
+    }
+
+    noise.set_frequency(cstgs.frequency);
+
+    let v = noise.get_noise(x as f32 / cstgs.divider, y as f32 / cstgs.divider);
+
+    v <= cstgs.size
+}
+
+/// Deepslate fills everything under [`DeepslateSettings::level`],
+/// the boundary being moved up and down by up to [`DeepslateSettings::transition`] blocks
+fn is_deepslate(x: i32, y: i32, noise: &mut FastNoise, dstgs: &DeepslateSettings) -> bool {
+    noise.set_frequency(dstgs.frequency);
+
+    let v = noise.get_noise(x as f32 / dstgs.divider, y as f32 / dstgs.divider);
+
+    (y as f32) + v * dstgs.transition < dstgs.level as f32
+}
+
+/// Returns the position of the chunk containing the column [`x`]
+pub fn chunk_of(x: i32) -> i32 {
+    (x - 1).div_euclid(CHUNK_SIZE)
+}
+
+fn is_ore(x: i32, y: i32, noise: &mut FastNoise, map_step: i32, ore_stgs: &OreSettings) -> bool {
+    if !inside(ore_stgs.below, ore_stgs.above, y) {
+        return false;
+    }
+
+    // Each ore reads its own part of the noise map so veins of different ores don't line up
+    let step = map_step * ore_stgs.kind.to_index() as i32;
+
+    noise.set_frequency(ore_stgs.rarity);
+    let v = noise.get_noise(
+        (x + step) as f32 / ore_stgs.divider,
+        (y + step) as f32 / ore_stgs.divider,
+    );
+
+    v < ore_stgs.size
+}
+
+fn is_blob(x: i32, y: i32, noise: &mut FastNoise, blob_stgs: &BlobSettings) -> bool {
+    if !inside(blob_stgs.below, blob_stgs.above, y) {
+        return false;
+    }
+
+    // Same as ores, each kind gets its own part of the noise map
+    let step = BLOB_MAP_STEP * blob_stgs.kind.to_index() as i32;
+
+    noise.set_frequency(blob_stgs.frequency);
+    let v = noise.get_noise(
+        (x + step) as f32 / blob_stgs.divider,
+        (y + step) as f32 / blob_stgs.divider,
+    );
+
+    v < blob_stgs.size
+}
+
+fn default_host() -> ItemKind {
+    ItemKind::Stone
+}
+
+/// Grows a tree from its root at [`x`] [`y`], in the space of [`data`].
+/// Its shape is rolled from [`seed`] so it is the same from every chunk it reaches,
+/// blocks falling outside of the grid are dropped
+fn spawn_tree(tree: &TreeSettings, seed: u64, x: i32, y: i32, data: &mut impl BlockGrid) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    // First cell above the trunk
+    let top = y + rng.gen_range(tree.min_height..=tree.max_height);
+
+    let canopy_bottom = match tree.canopy {
+        Canopy::None => top,
+        Canopy::Round { height, .. } | Canopy::Cone { height, .. } => (top - height).max(y + 1),
+    };
+
+    for i in y..top {
+        let kind = match tree.leafed_log {
+            Some(leafed_log) if i > canopy_bottom => leafed_log,
+            _ => tree.log,
+        };
+
+        set_if_empty(data, x, i, kind);
+    }
 
-            for i in -2..=2 {
-                for j in 0..=3 {
-                    if i == 0
-                        || blocks
-                            .iter()
-                            .any(|&b| b.x == x + i as f32 && b.y == y + j as f32 + 2.)
-                    {
+    // Branches grow out of the bare part of the trunk, leaving its two lowest blocks alone
+    for i in y + 2..canopy_bottom {
+        if !rng.gen_bool(tree.branch_chance as f64) {
+            continue;
+        }
+
+        let dir = if rng.gen_bool(0.5) { 1 } else { -1 };
+        let length = rng.gen_range(1..=2);
+        let rise = rng.gen_bool(0.5) as i32;
+
+        let end = IVec2::new(x + dir * length, i + rise * (length - 1));
+        for l in 1..=length {
+            set_if_empty(data, x + dir * l, i + rise * (l - 1), tree.log);
+        }
+
+        if let Some(leaves) = tree.leaves {
+            set_if_empty(data, end.x, end.y + 1, leaves);
+            set_if_empty(data, end.x + dir, end.y, leaves);
+            set_if_empty(data, end.x + dir, end.y + 1, leaves);
+        }
+    }
+
+    let Some(leaves) = tree.leaves else { return };
+
+    match tree.canopy {
+        Canopy::None => {}
+        Canopy::Round { radius, .. } => {
+            for j in canopy_bottom..top {
+                for i in -radius..=radius {
+                    // Corners are trimmed at random to round the canopy off
+                    let corner = i.abs() == radius && (j == canopy_bottom || j == top - 1);
+                    if corner && rng.gen_bool(0.5) {
                         continue;
                     }
 
-                    commands.spawn((
-                        BlockBundle::non_collidable(
-                            ItemKind::Leaves,
-                            vec2(
-                                (x + i as f32) * BLOCK_SIZE,
-                                (y + j as f32 + 2.) * BLOCK_SIZE,
-                            ),
-                            block_graphics,
-                        ),
-                        Name::new(format!("Block {}:{}", x + i as f32, y + j as f32)),
-                    ));
-
-                    blocks.push(vec2(x + i as f32, y + j as f32 + 2.));
+                    set_if_empty(data, x + i, j, leaves);
                 }
             }
 
-            for i in -1..=1 {
-                if blocks.iter().any(|&b| b.x == x + i as f32 && b.y == y + 6.) {
-                    continue;
-                }
+            for i in -(radius - 1)..=radius - 1 {
+                set_if_empty(data, x + i, top, leaves);
+            }
+        }
+        Canopy::Cone { radius, height } => {
+            let radius = rng.gen_range(radius - 1..=radius).max(1);
 
-                commands.spawn((
-                    BlockBundle::non_collidable(
-                        ItemKind::Leaves,
-                        vec2((x + i as f32) * BLOCK_SIZE, (y + 6.) * BLOCK_SIZE),
-                        block_graphics,
-                    ),
-                    Name::new(format!("Block {}:{}", x + i as f32, y + 6.)),
-                ));
+            // Narrows towards the top, every other row pulled in for a layered look
+            for j in canopy_bottom..=top {
+                let width = (top - j) * radius / height.max(1) - (top - j) % 2;
 
-                blocks.push(vec2(x + i as f32, y + 6.));
+                for i in -width..=width {
+                    set_if_empty(data, x + i, j, leaves);
+                }
             }
         }
-        Cactus => {
-            for i in 0..=2 {
-                commands.spawn((
-                    BlockBundle::non_collidable(
-                        ItemKind::Cactus,
-                        vec2(x * BLOCK_SIZE, (y + i as f32) * BLOCK_SIZE),
-                        block_graphics,
-                    ),
-                    Name::new(format!("Block {}:{}", x, y + i as f32)),
-                ));
+    }
+}
+
+/// Whether a log can be reached from the leaves at [`pos`] going through
+/// at most [`LeafDecaySettings::distance`] blocks of leaves.
+/// Leaves next to unloaded chunks are kept as their logs could be there
+fn is_leaf_attached(pos: IVec2, grid: &WorldGrid, stgs: &WorldSettings) -> bool {
+    let mut seen = HashSet::new();
+    let mut frontier = vec![pos];
+    seen.insert(pos);
+
+    for _ in 0..stgs.leaf_decay.distance {
+        let mut next = Vec::new();
+
+        for cell in frontier {
+            for dir in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let neighbour = cell + dir;
+                if !seen.insert(neighbour) {
+                    continue;
+                }
+
+                if !grid.is_loaded(neighbour) {
+                    return true;
+                }
+
+                let Some(kind) = grid.get_block(neighbour) else { continue };
+
+                for tree in stgs.trees.iter() {
+                    if tree.log == kind || tree.leafed_log == Some(kind) {
+                        return true;
+                    }
+
+                    if tree.leaves == Some(kind) {
+                        next.push(neighbour);
+                    }
+                }
             }
         }
+
+        frontier = next;
     }
+
+    false
 }
 
-fn spawn_test_platform(mut commands: Commands, block_graphics: Res<BlockGraphics>) {
+fn set_if_empty(data: &mut impl BlockGrid, x: i32, y: i32, kind: ItemKind) {
+    if data.get_block(x, y).is_none() {
+        data.set_block(x, y, Some(kind));
+    }
+}
+
+fn spawn_test_platform(mut commands: Commands) {
     commands
         .spawn((WorldBundle::default(), Name::new("World")))
         .with_children(|cb| {
@@ -524,13 +1096,11 @@ fn spawn_test_platform(mut commands: Commands, block_graphics: Res<BlockGraphics
                 cb.spawn(BlockBundle::new(
                     ItemKind::Grass,
                     vec2(60. * i as f32, -300.),
-                    &block_graphics,
                 ));
 
                 cb.spawn(BlockBundle::new(
                     ItemKind::Dirt,
                     vec2(60. * i as f32, -360.),
-                    &block_graphics,
                 ));
             }
         });
@@ -552,26 +1122,252 @@ fn spawn_test_platform(mut commands: Commands, block_graphics: Res<BlockGraphics
 
 // RESOURCES
 
-#[derive(Resource, Reflect, InspectorOptions)]
+#[derive(Resource, Clone, Deserialize, Reflect, TypeUuid, InspectorOptions)]
+#[uuid = "5f0c7f38-6a2e-4c3b-9a57-1b8e2d4c9e61"]
 struct WorldSettings {
     seed: u64,
     octaves: i32,
     lacunarity: f32,
 
+    deepslate: DeepslateSettings,
+    blobs: Vec<BlobSettings>,
+    lakes: LakeSettings,
+    sea_level: i32,
+    /// Open cells of the caves are filled with lava up to this height
+    lava_level: i32,
+    beach: BeachSettings,
+    density: DensitySettings,
+    /// Shape of the trees of each [`TreeKind`]
+    trees: Vec<TreeSettings>,
+    leaf_decay: LeafDecaySettings,
     biomes: Biomes,
 }
 
+impl WorldSettings {
+    /// Biomes of the list followed by the ocean
+    fn all_biomes(&self) -> impl Iterator<Item = &BiomeSettings> {
+        self.biomes
+            .list
+            .iter()
+            .chain(iter::once(&self.biomes.ocean))
+    }
+
+    /// Rejects the values world generation can't work with
+    fn validate(&self) -> Result<(), String> {
+        if self.biomes.list.is_empty() {
+            return Err("at least one biome is required".to_string());
+        }
+
+        if self.biomes.blend_width < 0 {
+            return Err("biome blend width can't be negative".to_string());
+        }
+
+        if self.lakes.radius < 1 {
+            return Err("lake radius must be at least 1".to_string());
+        }
+
+        if !(0. ..=1.).contains(&self.lakes.chance) {
+            return Err("lake chance is outside of 0 to 1".to_string());
+        }
+
+        if let Some(tree) = self
+            .trees
+            .iter()
+            .find(|tree| tree.min_height < 1 || tree.min_height > tree.max_height)
+        {
+            return Err(format!("{:?} trees have invalid heights", tree.kind));
+        }
+
+        if let Some(tree) = self
+            .trees
+            .iter()
+            .find(|tree| !(0. ..=1.).contains(&tree.branch_chance))
+        {
+            return Err(format!(
+                "{:?} trees have a branch chance outside of 0 to 1",
+                tree.kind
+            ));
+        }
+
+        if !(0. ..=1.).contains(&self.leaf_decay.sapling_chance) {
+            return Err("leaf decay sapling chance is outside of 0 to 1".to_string());
+        }
+
+        for biome in self.all_biomes() {
+            if let Some(plant) = biome
+                .plants
+                .iter()
+                .find(|plant| !(0. ..=1.).contains(&plant.density))
+            {
+                return Err(format!(
+                    "{:?} has a density outside of 0 to 1 in {}",
+                    plant.kind, biome.name
+                ));
+            }
+
+            if biome.worms.frequency < 0. {
+                return Err(format!("{} has a negative worm frequency", biome.name));
+            }
+
+            if !(0. ..=1.).contains(&biome.ravines.chance) {
+                return Err(format!(
+                    "{} has a ravine chance outside of 0 to 1",
+                    biome.name
+                ));
+            }
+
+            if let Some(kind) = biome
+                .trees
+                .iter()
+                .filter_map(|tree| tree.kind)
+                .find(|&kind| !self.trees.iter().any(|tree| tree.kind == kind))
+            {
+                return Err(format!("{:?} trees have no settings", kind));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rejects structures found in biomes that don't exist
+    fn validate_structures(&self, structures: &[Structure]) -> Result<(), String> {
+        for structure in structures {
+            if let Some(name) = structure
+                .biomes
+                .iter()
+                .find(|&name| !self.all_biomes().any(|biome| biome.name == *name))
+            {
+                return Err(format!(
+                    "{} is found in {}, which isn't a biome",
+                    structure.name, name
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Resource)]
 pub struct PlayerChunkPosition(pub i32);
 
+#[derive(Resource, Default)]
+struct WorldSettingsHandle(Handle<WorldSettings>);
+
 // STRUCTS
 
-#[derive(Reflect, InspectorOptions)]
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct DeepslateSettings {
+    level: i32,
+    transition: f32,
+    frequency: f32,
+    divider: f32,
+}
+
+/// Pocket of [`BlobSettings::kind`] replacing the [`BlobSettings::host`] block
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct BlobSettings {
+    kind: ItemKind,
+    #[serde(default = "default_host")]
+    host: ItemKind,
+    frequency: f32,
+    size: f32,
+    divider: f32,
+    below: Option<i32>,
+    above: Option<i32>,
+}
+
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct LakeSettings {
+    /// Chance for a depression of the terrain to be filled, from 0 to 1
+    chance: f32,
+    /// How far from its bottom a lake looks for its rims
+    radius: i32,
+    max_depth: i32,
+    /// Thickness of the [`BiomeSettings::lake_bed`] layer under the water
+    bed_depth: i32,
+}
+
+/// Large open caves cut out of the noise map
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct CavernSettings {
+    frequency: f32,
+    divider: f32,
+    /// Highest noise value that is carved
+    size: f32,
+    below: Option<i32>,
+    above: Option<i32>,
+}
+
+/// Tunnels walking away from random points of the chunks, across chunk borders
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct WormSettings {
+    /// Average number of worms starting in each chunk
+    frequency: f32,
+    /// Blocks walked by each worm
+    length: i32,
+    radius: f32,
+    /// Bounds of the height worms start at
+    below: Option<i32>,
+    above: Option<i32>,
+}
+
+/// Deep narrow cracks going down from the surface
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct RavineSettings {
+    /// Chance for each chunk to have a ravine, from 0 to 1
+    chance: f32,
+    depth: i32,
+    width: f32,
+    /// Highest point of the ravines, they start at the surface otherwise
+    below: Option<i32>,
+    /// Lowest point of the ravines
+    above: Option<i32>,
+}
+
+/// Noise shaping the ground of the biomes with a [`BiomeSettings::density`]
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct DensitySettings {
+    frequency: f32,
+    divider: f32,
+}
+
+/// Sand band covering the columns whose surface is close to the sea level
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct BeachSettings {
+    /// How far above the sea level beaches go
+    height: i32,
+    /// How far under the sea level beaches go
+    depth: i32,
+    block: ItemKind,
+}
+
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
 struct Biomes {
     frequency: f32,
     divider: f32,
-    plain: BiomeSettings,
-    // desert: BiomeSettings,
+    /// How many columns on each side of a biome edge get blended
+    blend_width: i32,
+    /// Sorted by ascending [`BiomeSettings::v`] once loaded
+    list: Vec<BiomeSettings>,
+
+    /// Continent noise is much slower than the biome one so oceans span many chunks
+    continent_frequency: f32,
+    continent_divider: f32,
+    /// Highest continent noise value the ocean is picked for
+    ocean_v: f32,
+    ocean: BiomeSettings,
+}
+
+/// What generation needs to know about a column before placing its blocks
+struct Column<'a> {
+    /// Biome the column belongs to
+    biome: &'a BiomeSettings,
+    /// Biome the surface blocks and trees are taken from
+    surface: &'a BiomeSettings,
+    height: i32,
+    /// Blended [`BiomeSettings::density`] of the biomes around the column
+    density: f32,
 }
 
 /// Frequency compresses values in the x axis
@@ -583,41 +1379,53 @@ struct Biomes {
 /// The greater the divider, the less varied the heights are
 /// div = 1 -> 013420035335601
 /// div = 2 -> 001234443332100
-#[derive(Reflect, InspectorOptions)]
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
 struct BiomeSettings {
+    name: String,
+    /// Highest biome noise value the biome is picked for, unused by the ocean
+    #[serde(default)]
     v: f32,
 
     terrain_frequency: f32,
     terrain_divider: f32,
-    cave_frequency: f32,
-    cave_divider: f32,
 
     height_multiplier: f32,
     height_addition: f32,
+    /// How many blocks the density noise can move the ground by,
+    /// 0 keeps a plain height map while more gives cliffs and overhangs
+    #[serde(default)]
+    density: f32,
+    /// Surface blocks are left out above this height
+    #[serde(default)]
+    peak_height: Option<i32>,
+
+    caverns: CavernSettings,
+    worms: WormSettings,
+    ravines: RavineSettings,
 
-    air_porbality: f32,
     exposed_block_top_layer_height: i32,
     exposed_block_layer_height: i32,
-    tree_kind: TreeKind,
-    /// The greater the rarer
-    tree_chance: i32,
+    /// Tree rolled for each column of the surface
+    trees: Vec<WeightedTree>,
+    /// Rolled in order for each column of the surface, the first one passing is placed
+    #[serde(default)]
+    plants: Vec<PlantSettings>,
 
     exposed_block_top: ItemKind,
     exposed_block: ItemKind,
+    lake_bed: ItemKind,
 
     ores_map_step: i32,
-    coal: OreSettings,
-    copper: OreSettings,
-    iron: OreSettings,
-    gold: OreSettings,
-    // lapis: OreSettings,
-    // redstone: OreSettings,
-    // emrald: OreSettings,
-    diamond: OreSettings,
+    /// Ordered by priority, the first ore found in a cell is the one placed
+    ores: Vec<OreSettings>,
 }
 
-#[derive(Reflect, InspectorOptions)]
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
 struct OreSettings {
+    kind: ItemKind,
+    /// Block the ore replaces
+    #[serde(default = "default_host")]
+    host: ItemKind,
     rarity: f32,
     size: f32,
     divider: f32,
@@ -625,12 +1433,115 @@ struct OreSettings {
     above: Option<i32>,
 }
 
-#[derive(Clone, Copy, Reflect)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Reflect)]
 enum TreeKind {
     Oak,
+    Birch,
+    Spruce,
+    Jungle,
     Cactus,
 }
 
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct PlantSettings {
+    kind: ItemKind,
+    /// Chance for each column to get the plant, from 0 to 1
+    density: f32,
+    /// Blocks the plant can stand on
+    grounds: Vec<ItemKind>,
+}
+
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct LeafDecaySettings {
+    /// Most leaves between a log and the leaves it keeps from decaying
+    distance: i32,
+    /// Chance for decaying leaves to drop the sapling of their tree, from 0 to 1
+    sapling_chance: f32,
+}
+
+/// Tree with a chance to be picked proportional to its weight
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct WeightedTree {
+    /// [`None`] grows no tree
+    kind: Option<TreeKind>,
+    weight: u32,
+}
+
+#[derive(Clone, Deserialize, Reflect, InspectorOptions)]
+struct TreeSettings {
+    kind: TreeKind,
+    /// Block growing into the tree when randomly ticked
+    #[serde(default)]
+    sapling: Option<ItemKind>,
+    log: ItemKind,
+    /// Log used for the trunk inside the canopy, [`TreeSettings::log`] if missing
+    #[serde(default)]
+    leafed_log: Option<ItemKind>,
+    #[serde(default)]
+    leaves: Option<ItemKind>,
+    /// Bounds of the trunk height, both included
+    min_height: i32,
+    max_height: i32,
+    canopy: Canopy,
+    /// Chance for each bare block of the trunk to grow a branch, from 0 to 1
+    #[serde(default)]
+    branch_chance: f32,
+}
+
+#[derive(Clone, Copy, Deserialize, Reflect)]
+enum Canopy {
+    None,
+    /// Block of leaves around the top of the trunk, capped by a narrower row
+    Round {
+        radius: i32,
+        height: i32,
+    },
+    /// Leaves narrowing from [`radius`] at the bottom to a single block above the trunk
+    Cone {
+        radius: i32,
+        height: i32,
+    },
+}
+
+// ASSET LOADERS
+
+#[derive(Default)]
+struct WorldSettingsLoader;
+
+impl AssetLoader for WorldSettingsLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let path = load_context.path().display();
+
+            let settings = parse_world_settings(bytes).map_err(|err| {
+                bevy::asset::Error::msg(format!("Invalid world settings in {}: {}", path, err))
+            })?;
+
+            load_context.set_default_asset(LoadedAsset::new(settings));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["settings.ron"]
+    }
+}
+
+/// Reads and checks world settings, biomes being sorted by [`v`] as world generation expects
+fn parse_world_settings(bytes: &[u8]) -> Result<WorldSettings, String> {
+    let mut settings =
+        ron::de::from_bytes::<WorldSettings>(bytes).map_err(|err| err.to_string())?;
+
+    settings.validate()?;
+    settings.biomes.list.sort_by(|a, b| a.v.total_cmp(&b.v));
+
+    Ok(settings)
+}
+
 // BUNDLES
 
 #[derive(Bundle)]
@@ -646,6 +1557,10 @@ struct WorldBundle {
 struct ChunkBundle {
     // game related
     chunk_position: ChunkPosition,
+    data: ChunkData,
+
+    // rendering
+    view: ChunkView,
 
     // tags
     tag: Chunk,
@@ -667,9 +1582,11 @@ impl Default for WorldBundle {
 }
 
 impl ChunkBundle {
-    fn new(x: i32) -> Self {
+    fn new(x: i32, data: ChunkData) -> Self {
         Self {
             chunk_position: ChunkPosition(x),
+            data,
+            view: default(),
             tag: Chunk,
             spatial_bundle: default(),
         }
@@ -680,8 +1597,87 @@ impl Default for ChunkBundle {
     fn default() -> Self {
         Self {
             chunk_position: ChunkPosition(0),
+            data: default(),
+            view: default(),
             tag: Chunk,
             spatial_bundle: default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Settings as the loader gives them
+    fn settings() -> WorldSettings {
+        parse_world_settings(include_bytes!("../assets/world.settings.ron")).unwrap()
+    }
+
+    fn structures() -> Vec<Structure> {
+        [
+            include_str!("../assets/structures/desert_well.structure.ron"),
+            include_str!("../assets/structures/dungeon.structure.ron"),
+            include_str!("../assets/structures/ruins.structure.ron"),
+        ]
+        .into_iter()
+        .map(|source| ron::from_str(source).unwrap())
+        .collect()
+    }
+
+    fn assert_same_cells(a: &ChunkData, b: &ChunkData) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_HEIGHT {
+                assert_eq!(a.get_block(x, y), b.get_block(x, y), "block at {}:{}", x, y);
+                assert_eq!(a.get_wall(x, y), b.get_wall(x, y), "wall at {}:{}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn world_settings_load() {
+        if let Err(err) = parse_world_settings(include_bytes!("../assets/world.settings.ron")) {
+            panic!("Invalid world settings: {}", err);
+        }
+    }
+
+    #[test]
+    fn structures_are_found_in_existing_biomes() {
+        if let Err(err) = settings().validate_structures(&structures()) {
+            panic!("Invalid structures: {}", err);
+        }
+    }
+
+    #[test]
+    fn regenerating_a_chunk_gives_the_same_cells() {
+        let stgs = settings();
+        let structures = structures();
+
+        let first = generate_chunk(3, &stgs, &structures, None);
+        let second = generate_chunk(3, &stgs, &structures, None);
+        assert_same_cells(&first, &second);
+    }
+
+    #[test]
+    fn chunk_trees_dont_depend_on_the_chunks_generated_before() {
+        let stgs = settings();
+        let structures = structures();
+        let chunks = -4..4;
+
+        let fresh: Vec<_> = chunks
+            .clone()
+            .map(|cx| chunk_trees(cx, &mut world_noise(&stgs), &stgs))
+            .collect();
+        assert!(fresh.iter().any(|trees| !trees.is_empty()));
+
+        // Every step of generation changes the frequency of the shared noise
+        let mut noise = world_noise(&stgs);
+        for (cx, trees) in chunks.rev().zip(fresh.iter().rev()) {
+            chunk_structures(cx + 1, &mut noise, &stgs, &structures);
+            carved_cells(cx - 1, &mut noise, &stgs);
+
+            let again = chunk_trees(cx, &mut noise, &stgs);
+            assert_eq!(&again, trees, "trees of chunk {}", cx);
+        }
+    }
+}